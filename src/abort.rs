@@ -0,0 +1,199 @@
+//! The module that contains the cancellation of requests.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::task::{self, Task};
+
+/// A handle that cancels the requests it is attached to.
+///
+/// Aborting stops the cURL transfers right away and makes their futures
+/// fail with [`Error::Cancelled`](enum.Error.html#variant.Cancelled).
+/// Requests attached to an aborted handle fail as soon as they are sent.
+/// Clones of a handle abort the same requests.
+///
+/// The session removes cancelled transfers the next time its event loop
+/// runs.
+///
+/// ```rust,ignore
+/// let abort = AbortHandle::new();
+/// let future = get(&url).abort_handle(&abort).send(evloop.handle());
+/// // Later, e.g. on shutdown:
+/// abort.abort();
+/// ```
+#[derive(Clone, Default)]
+pub struct AbortHandle {
+    inner: Arc<Inner>
+}
+
+/// The state shared between the clones of a handle.
+#[derive(Default)]
+struct Inner {
+    aborted: AtomicBool,
+    next_id: AtomicUsize,
+    tasks: Mutex<HashMap<usize, Task>>
+}
+
+/// The registration of a transfer with an `AbortHandle`, used to wake up
+/// the task driving the transfer once the handle is aborted.
+pub(crate) struct Registration {
+    handle: AbortHandle,
+    id: usize
+}
+
+impl AbortHandle {
+    /// Creates a new handle that has not been aborted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all requests attached to this handle.
+    pub fn abort(&self) {
+        self.inner.aborted.store(true, Ordering::SeqCst);
+        let tasks = self.inner.tasks.lock().unwrap().drain().collect::<Vec<_>>();
+        for (_, task) in tasks {
+            task.notify();
+        }
+    }
+
+    /// Checks whether the handle has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Registers a transfer with this handle.
+    pub(crate) fn register(&self) -> Registration {
+        Registration {
+            handle: self.clone(),
+            id: self.inner.next_id.fetch_add(1, Ordering::SeqCst)
+        }
+    }
+}
+
+impl Debug for AbortHandle {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(AbortHandle))
+            .field("aborted", &self.is_aborted())
+            .finish()
+    }
+}
+
+impl Registration {
+    /// Checks whether the handle has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.handle.is_aborted()
+    }
+
+    /// Checks whether the handle has been aborted and arranges for the
+    /// current task to be notified if it is later.
+    pub fn poll_aborted(&self) -> bool {
+        if self.handle.is_aborted() {
+            return true;
+        }
+        self.handle.inner.tasks.lock().unwrap().insert(self.id, task::current());
+        // The handle may have been aborted before the task was stored.
+        self.handle.is_aborted()
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.handle.inner.tasks.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use tokio_core::reactor::{Core, Timeout};
+
+    use error::Error;
+    use str::get;
+    use test_server::serve;
+    use super::AbortHandle;
+
+    /// Keeps the event loop running for a bit, so that the session can
+    /// remove the cancelled transfers.
+    fn settle(evloop: &mut Core) {
+        let timeout = Timeout::new(Duration::from_millis(500), &evloop.handle()).unwrap();
+        evloop.run(timeout).unwrap();
+    }
+
+    /// Returns the URL of a server that trickles out a body for ten seconds,
+    /// and a channel that receives once the client hung up.
+    fn slow_server() -> (String, ::std::sync::mpsc::Receiver<()>) {
+        let (tx, rx) = channel();
+        let url = serve(move |_, stream| {
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n");
+            for _ in 0..100 {
+                thread::sleep(Duration::from_millis(100));
+                if stream.write_all(b"x").and_then(|_| stream.flush()).is_err() {
+                    let _ = tx.send(());
+                    return;
+                }
+            }
+        });
+        (url.into_string(), rx)
+    }
+
+    #[test]
+    fn cancels_in_flight_transfer() {
+        let (url, hung_up) = slow_server();
+        let abort = AbortHandle::new();
+        let remote_abort = abort.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            remote_abort.abort();
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let start = Instant::now();
+        let future = get(&url).abort_handle(&abort).send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Cancelled) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // The server notices that the transfer is gone.
+        settle(&mut evloop);
+        hung_up.recv_timeout(Duration::from_secs(3)).unwrap();
+    }
+
+    #[test]
+    fn cancels_streaming_transfer() {
+        let (url, _) = slow_server();
+        let abort = AbortHandle::new();
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(&url).abort_handle(&abort).send_streaming(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        abort.abort();
+        match evloop.run(::futures::Stream::collect(response.body())) {
+            Err(Error::Cancelled) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+        settle(&mut evloop);
+    }
+
+    #[test]
+    fn fails_when_already_aborted() {
+        let (url, _) = slow_server();
+        let abort = AbortHandle::new();
+        abort.abort();
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(&url).abort_handle(&abort).send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Cancelled) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+        settle(&mut evloop);
+    }
+}
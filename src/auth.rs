@@ -0,0 +1,148 @@
+//! The module that contains the authentication schemes.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+use curl::easy::{Auth, Easy};
+use error::Error;
+use header::{self, HeaderMap};
+
+/// The credentials a request authenticates itself with.
+#[derive(Clone)]
+pub(crate) enum Credentials {
+    /// A user name and password sent with every request.
+    Basic(String, String),
+    /// A token sent with every request.
+    Bearer(String),
+    /// A user name and password used to answer a digest challenge.
+    Digest(String, String),
+    /// A user name and password used in an NTLM handshake.
+    Ntlm(String, String)
+}
+
+impl Credentials {
+    /// Gets the `Authorization` header for schemes cURL does not handle
+    /// itself.
+    ///
+    /// Fails with `Error::InvalidHeader` if the token would break the header
+    /// apart.
+    pub fn header(&self) -> Result<Option<String>, Error> {
+        match *self {
+            Credentials::Bearer(ref token) => {
+                let token = header::validate("Authorization", token)?;
+                Ok(Some(format!("Authorization: Bearer {}", token)))
+            },
+            _ => Ok(None)
+        }
+    }
+
+    /// Configures the given handle to authenticate with these credentials.
+    pub fn configure(&self, easy: &mut Easy) -> Result<(), Error> {
+        let (user, password, auth) = match *self {
+            Credentials::Basic(ref user, ref password) => (user, password, Auth::new().basic(true).clone()),
+            Credentials::Bearer(_) => return Ok(()),
+            Credentials::Digest(ref user, ref password) => (user, password, Auth::new().digest(true).clone()),
+            Credentials::Ntlm(ref user, ref password) => (user, password, Auth::new().ntlm(true).clone())
+        };
+        easy.username(user)?;
+        easy.password(password)?;
+        easy.http_auth(&auth)?;
+        Ok(())
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        // Keep the secrets out of logs.
+        match *self {
+            Credentials::Basic(ref user, _) => write!(fmt, "Basic({:?}, \"[redacted]\")", user),
+            Credentials::Bearer(_) => write!(fmt, "Bearer(\"[redacted]\")"),
+            Credentials::Digest(ref user, _) => write!(fmt, "Digest({:?}, \"[redacted]\")", user),
+            Credentials::Ntlm(ref user, _) => write!(fmt, "Ntlm({:?}, \"[redacted]\")", user)
+        }
+    }
+}
+
+/// Replaces the values of headers carrying credentials for logging.
+pub(crate) fn redact_headers(headers: &HeaderMap) -> Vec<(&str, &str)> {
+    headers.iter()
+        .map(|(name, value)| {
+            if name.eq_ignore_ascii_case("Authorization") || name.eq_ignore_ascii_case("Proxy-Authorization") {
+                (name, "[redacted]")
+            } else {
+                (name, value)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve};
+
+    /// Returns the URL of a server that echoes the `Authorization` header.
+    fn echo_auth() -> ::url::Url {
+        serve(|request, stream| {
+            let auth = request.header("Authorization").unwrap_or("-").to_owned();
+            respond(stream, "200 OK", &[], auth.as_bytes());
+        })
+    }
+
+    #[test]
+    fn sends_basic_and_bearer_credentials() {
+        let url = echo_auth();
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str()).basic_auth("user", "pass").send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().body_str(), Some("Basic dXNlcjpwYXNz"));
+
+        let future = get(url.as_str()).bearer_auth("t0k3n").send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().body_str(), Some("Bearer t0k3n"));
+    }
+
+    #[test]
+    fn rejects_bearer_token_with_line_break() {
+        let url = echo_auth();
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str()).bearer_auth("t0k3n\r\nX-Injected: yes").send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::InvalidHeader(name)) => assert_eq!(name, "Authorization"),
+            res => panic!("Expected an invalid header error, got {:?}", res)
+        }
+    }
+
+    #[test]
+    fn answers_digest_challenge() {
+        let url = serve(|request, stream| {
+            match request.header("Authorization") {
+                Some(auth) if auth.starts_with("Digest username=\"user\"") => {
+                    respond(stream, "200 OK", &[], b"welcome")
+                },
+                _ => respond(stream, "401 Unauthorized", &[
+                    ("WWW-Authenticate", "Digest realm=\"test\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", qop=\"auth\"")
+                ], b"")
+            }
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(url.as_str()).digest_auth("user", "pass").send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.body_str(), Some("welcome"));
+    }
+
+    #[test]
+    fn debug_redacts_credentials() {
+        let request = get("http://example.com/")
+            .basic_auth("user", "hunter2")
+            .header("Authorization", "Bearer s3cr3t");
+        let debug = format!("{:?}", request);
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("s3cr3t"));
+    }
+}
@@ -0,0 +1,331 @@
+//! The module that contains the code for request bodies.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use curl::easy::{Easy, ReadError, SeekResult};
+use error::Error;
+use futures::{Async, Poll, Stream};
+use futures::task::{self, Task};
+use multipart::Multipart;
+use stream::STREAM_BUFFER_SIZE;
+use transfer::Resume;
+
+/// A boxed stream of request body chunks.
+type BodyChunks = Box<dyn Stream<Item = Bytes, Error = io::Error> + Send>;
+
+/// The source of a request body.
+pub(crate) enum Body {
+    /// The body is in memory already.
+    Bytes(Vec<u8>),
+    /// The body is read from a file on disk.
+    File(PathBuf),
+    /// The body is a multipart form whose files are read from disk.
+    Multipart(Multipart),
+    /// The body is read from an arbitrary reader.
+    Reader(Box<dyn Read + Send>, Option<u64>),
+    /// The body is produced asynchronously.
+    Stream(BodyChunks, Option<u64>)
+}
+
+/// Feeds the chunks of a body stream to the read callback of a transfer.
+pub(crate) struct Upload {
+    chunks: BodyChunks,
+    state: Arc<Mutex<UploadState>>
+}
+
+/// The state shared between the read callback and the `Upload`.
+#[derive(Default)]
+struct UploadState {
+    buffered: usize,
+    chunks: VecDeque<Bytes>,
+    finished: bool,
+    paused: bool,
+    task: Option<Task>
+}
+
+impl Body {
+    /// Gets the length of the body, if it is known up front.
+    pub fn len(&self) -> Option<u64> {
+        match *self {
+            Body::Bytes(ref bytes) => Some(bytes.len() as u64),
+            Body::File(ref path) => path.metadata().ok().map(|meta| meta.len()),
+            Body::Multipart(ref form) => form.len(),
+            Body::Reader(_, len) | Body::Stream(_, len) => len
+        }
+    }
+
+    /// Clones the body, unless it is a reader or a stream that cannot be
+    /// read twice.
+    pub fn try_clone(&self) -> Option<Body> {
+        match *self {
+            Body::Bytes(ref bytes) => Some(Body::Bytes(bytes.clone())),
+            Body::File(ref path) => Some(Body::File(path.clone())),
+            Body::Multipart(ref form) => Some(Body::Multipart(form.clone())),
+            Body::Reader(..) | Body::Stream(..) => None
+        }
+    }
+
+    /// Configures the given handle to send this body.
+    ///
+    /// Returns whether the body needs to be sent with chunked transfer
+    /// encoding, and the `Upload` that needs to be driven along with the
+    /// transfer if the body is a stream.
+    pub fn configure(self, easy: &mut Easy) -> Result<(bool, Option<Upload>), Error> {
+        match self {
+            Body::Bytes(bytes) => {
+                easy.post_fields_copy(&bytes)?;
+                Ok((false, None))
+            },
+            Body::File(path) => {
+                let mut file = File::open(path)?;
+                let len = file.metadata()?.len();
+                let mut seek_file = file.try_clone()?;
+
+                easy.post(true)?;
+                easy.post_field_size(len)?;
+                easy.read_function(move |buf| file.read(buf).map_err(|_| ReadError::Abort))?;
+                easy.seek_function(move |pos| match seek_file.seek(pos) {
+                    Ok(_) => SeekResult::Ok,
+                    Err(_) => SeekResult::Fail
+                })?;
+                Ok((false, None))
+            },
+            Body::Multipart(form) => {
+                let (reader, len) = form.into_reader()?;
+                let reader = Arc::new(Mutex::new(reader));
+                let seek_reader = reader.clone();
+
+                easy.post(true)?;
+                easy.post_field_size(len)?;
+                easy.read_function(move |buf| {
+                    reader.lock().unwrap().read(buf).map_err(|_| ReadError::Abort)
+                })?;
+                easy.seek_function(move |pos| match seek_reader.lock().unwrap().seek(pos) {
+                    Ok(_) => SeekResult::Ok,
+                    Err(_) => SeekResult::Fail
+                })?;
+                Ok((false, None))
+            },
+            Body::Reader(mut reader, len) => {
+                easy.post(true)?;
+                if let Some(len) = len {
+                    easy.post_field_size(len)?;
+                }
+                easy.read_function(move |buf| reader.read(buf).map_err(|_| ReadError::Abort))?;
+                Ok((len.is_none(), None))
+            },
+            Body::Stream(chunks, len) => {
+                let state = Arc::new(Mutex::new(UploadState::default()));
+                let read_state = state.clone();
+
+                easy.post(true)?;
+                if let Some(len) = len {
+                    easy.post_field_size(len)?;
+                }
+                easy.read_function(move |buf| {
+                    let mut state = read_state.lock().unwrap();
+                    let read = state.read(buf);
+                    if read == 0 && !state.finished {
+                        // cURL calls us again once the transfer is resumed.
+                        state.paused = true;
+                        return Err(ReadError::Pause);
+                    }
+                    if let Some(task) = state.task.take() {
+                        task.notify();
+                    }
+                    Ok(read)
+                })?;
+
+                let upload = Upload {
+                    chunks,
+                    state
+                };
+                Ok((len.is_none(), Some(upload)))
+            }
+        }
+    }
+}
+
+impl Upload {
+    /// Polls the body stream for new chunks until either the stream has
+    /// ended or enough data is buffered, and resumes the transfer if it
+    /// waits for them.
+    pub fn poll(&mut self, resume: &mut Resume) -> Poll<(), io::Error> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.buffered >= STREAM_BUFFER_SIZE {
+                    state.task = Some(task::current());
+                    return Ok(Async::NotReady);
+                }
+            }
+
+            let chunk = match self.chunks.poll()? {
+                Async::Ready(chunk) => chunk,
+                Async::NotReady => return Ok(Async::NotReady)
+            };
+            let finished = chunk.is_none();
+            let paused = {
+                let mut state = self.state.lock().unwrap();
+                match chunk {
+                    Some(chunk) => {
+                        state.buffered += chunk.len();
+                        state.chunks.push_back(chunk);
+                    },
+                    None => state.finished = true
+                }
+                let paused = state.paused;
+                state.paused = false;
+                paused
+            };
+            if paused {
+                resume.resume();
+            }
+            if finished {
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+impl UploadState {
+    /// Moves as much of the buffered data as possible into the given buffer.
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut read = 0;
+        while read < buf.len() {
+            let mut chunk = match self.chunks.pop_front() {
+                Some(chunk) => chunk,
+                None => break
+            };
+            let len = chunk.len().min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&chunk[..len]);
+            read += len;
+            if len < chunk.len() {
+                self.chunks.push_front(chunk.split_off(len));
+            }
+        }
+        self.buffered -= read;
+        read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, Cursor, Error, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc as std_mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures::Stream;
+    use futures::sync::mpsc;
+    use tokio_core::reactor::Core;
+
+    use str::post;
+    use test_server::{respond, serve, TempPath};
+
+    fn echo_server() -> String {
+        serve(|request, stream| {
+            let chunked = request.header("Transfer-Encoding").is_some_and(|te| te == "chunked");
+            let mut body = request.body;
+            body.extend_from_slice(if chunked { b" (chunked)" } else { b"" });
+            respond(stream, "200 OK", &[], &body);
+        }).into_string()
+    }
+
+    #[test]
+    fn stream_body_is_sent_chunked() {
+        let url = echo_server();
+        let (tx, rx) = mpsc::unbounded();
+        thread::spawn(move || {
+            // Make the transfer wait for the stream every now and then
+            for i in 0..100 {
+                tx.unbounded_send(Bytes::from(format!("{},", i))).unwrap();
+                if i % 10 == 0 {
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }
+        });
+        let expected: String = (0..100).map(|i| format!("{},", i)).collect();
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .body_stream(rx.map_err(|_| Error::other("sender gone")), None)
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some(&*format!("{} (chunked)", expected)));
+    }
+
+    #[test]
+    fn stream_body_is_sent_as_it_is_produced() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (received_tx, received_rx) = std_mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            let mut body = [0; 12];
+            reader.read_exact(&mut body[..6]).unwrap();
+            received_tx.send(()).unwrap();
+            reader.read_exact(&mut body[6..]).unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\n").unwrap();
+            stream.write_all(&body).unwrap();
+        });
+
+        let (tx, rx) = mpsc::unbounded();
+        let producer = thread::spawn(move || {
+            tx.unbounded_send(Bytes::from("first,")).unwrap();
+            // The stream only ends once the server has seen the first chunk
+            let received = received_rx.recv_timeout(Duration::from_secs(5)).is_ok();
+            tx.unbounded_send(Bytes::from("second")).unwrap();
+            received
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .body_stream(rx.map_err(|_| Error::other("sender gone")), Some(12))
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert!(producer.join().unwrap(), "the first chunk was not sent before the stream ended");
+        assert_eq!(response.body_str(), Some("first,second"));
+    }
+
+    #[test]
+    fn reader_body_with_length() {
+        let url = echo_server();
+        let body = vec![b'x'; 100_000];
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .body_reader(Cursor::new(body.clone()), Some(body.len() as u64))
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body(), &body[..]);
+    }
+
+    #[test]
+    fn file_body() {
+        let url = echo_server();
+        let path = TempPath::new("file-body");
+        File::create(&path).unwrap().write_all(b"Hello from a file").unwrap();
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .body_file(&path)
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("Hello from a file"));
+    }
+}
@@ -0,0 +1,460 @@
+//! The module that contains the reusable HTTP client.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::path::Path;
+use std::time::Duration;
+
+use auth;
+use cookie::CookieJar;
+use download::Download;
+use error::Error;
+use header::HeaderMap;
+use pool::HandlePool;
+use proxy::{EnvProxies, Proxy};
+use request::{LOW_SPEED_LIMIT, LOW_SPEED_TIME, MAX_REDIRECTS, Request, ResponseFuture};
+use retry::RetryPolicy;
+use stream::SendStreaming;
+use tokio_core::reactor::{Handle, Remote};
+use tls::TlsConfig;
+use tokio_curl::Session;
+use url::Url;
+use {BoxFuture, Method};
+
+/// A reusable HTTP client.
+///
+/// A client owns a `Session` all of its requests are sent through and a
+/// [`HandlePool`](struct.HandlePool.html) their cURL handles are taken from,
+/// so connections can be reused between requests. It also holds defaults
+/// like headers, URL parameters and timeouts that every request created
+/// from the client inherits. Setting them on the request overrides the
+/// default.
+///
+/// ```rust,ignore
+/// let client = Client::new(evloop.handle())
+///                  .base_url(&Url::parse("https://httpbin.org/").unwrap())
+///                  .header("User-Agent", "tokio-request")
+///                  .timeout(Duration::from_secs(20));
+/// let future = client.send(client.get("get").param("Hello", "This is Rust"));
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    base_url: Option<Url>,
+    connect_timeout: Option<Duration>,
+    cookie_jar: Option<CookieJar>,
+    dns_timeout: Option<Duration>,
+    env_proxies: Option<EnvProxies>,
+    follow_redirects: bool,
+    headers: HeaderMap,
+    invalid_header: Option<String>,
+    lowspeed_limits: Option<(u32, Duration)>,
+    max_redirects: u32,
+    params: Vec<(String, String)>,
+    pool: HandlePool,
+    proxy: Option<Proxy>,
+    read_timeout: Option<Duration>,
+    remote: Remote,
+    retry: Option<RetryPolicy>,
+    session: Session,
+    timeout: Option<Duration>,
+    tls: Option<TlsConfig>
+}
+
+impl Client {
+    /// Creates a new client with a new `Session` on the specified event loop.
+    pub fn new(h: Handle) -> Self {
+        let session = Session::new(h.clone());
+        Self::with_session(h, session)
+    }
+
+    /// Creates a new client that sends its requests through the given `Session`.
+    ///
+    /// The event loop is used to wait between retries.
+    pub fn with_session(h: Handle, session: Session) -> Self {
+        Client {
+            base_url: None,
+            connect_timeout: None,
+            cookie_jar: None,
+            dns_timeout: None,
+            env_proxies: None,
+            follow_redirects: true,
+            headers: HeaderMap::new(),
+            invalid_header: None,
+            lowspeed_limits: Some((LOW_SPEED_LIMIT, Duration::from_secs(LOW_SPEED_TIME as u64))),
+            max_redirects: MAX_REDIRECTS,
+            params: Vec::new(),
+            pool: HandlePool::default(),
+            proxy: None,
+            read_timeout: None,
+            remote: h.remote().clone(),
+            retry: None,
+            session,
+            timeout: None,
+            tls: None
+        }
+    }
+
+    /// Sets the URL relative paths given to the request methods are resolved
+    /// against.
+    ///
+    /// Mind the trailing slash: resolving `users` against `https://example.com/api/`
+    /// yields `https://example.com/api/users`, while resolving it against
+    /// `https://example.com/api` yields `https://example.com/users`.
+    pub fn base_url(mut self, url: &Url) -> Self {
+        self.base_url = Some(url.clone());
+        self
+    }
+
+    /// Sets the cookie jar shared by the requests of this client.
+    ///
+    /// Requests can opt out with
+    /// [`Request::without_cookie_jar`](struct.Request.html#method.without_cookie_jar).
+    pub fn cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookie_jar = Some(jar);
+        self
+    }
+
+    /// Sets the default maximum time establishing a connection may take.
+    pub fn connect_timeout(mut self, duration: Duration) -> Self {
+        self.connect_timeout = Some(duration);
+        self
+    }
+
+    /// Sets the default maximum time resolving a host name may take.
+    pub fn dns_timeout(mut self, duration: Duration) -> Self {
+        self.dns_timeout = Some(duration);
+        self
+    }
+
+    /// Sets whether requests follow 3xx-redirects by default.
+    ///
+    /// Defaults to `true`.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// Adds a default HTTP header.
+    ///
+    /// Requests can leave it out with
+    /// [`Request::without_header`](struct.Request.html#method.without_header).
+    /// If the name or value is invalid, sending the requests of this client
+    /// fails with [`Error::InvalidHeader`](enum.Error.html#variant.InvalidHeader).
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if self.headers.append(name, value).is_err() && self.invalid_header.is_none() {
+            self.invalid_header = Some(name.to_owned());
+        }
+        self
+    }
+
+    /// Sets the default low speed thresholds.
+    ///
+    /// See [`Request::lowspeed_limit`](struct.Request.html#method.lowspeed_limit)
+    /// for more information.
+    pub fn lowspeed_limit(mut self, bytes: u32, per_duration: Duration) -> Self {
+        self.lowspeed_limits = if bytes > 0 && per_duration > Duration::from_secs(0) {
+            Some((bytes, per_duration))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Sets the default maximum amount of redirects to follow.
+    pub fn max_redirects(mut self, max_redirects: u32) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Adds a default URL parameter.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets the pool the cURL handles of the requests are taken from.
+    ///
+    /// Defaults to a pool with [`DEFAULT_POOL_SIZE`](constant.DEFAULT_POOL_SIZE.html)
+    /// handles.
+    pub fn pool(mut self, pool: HandlePool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Sends all requests through the given proxy.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.env_proxies = None;
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Sends the requests through the proxies set in the environment.
+    ///
+    /// The `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` variables
+    /// (or their lowercase variants, which take precedence) are read once,
+    /// when this method is called. Requests to URLs no proxy is set for are
+    /// sent directly, instead of leaving it to cURL to look at the
+    /// environment.
+    pub fn proxy_from_env(self) -> Self {
+        self.env_proxies(EnvProxies::from_env())
+    }
+
+    /// Sends the requests through the given proxies from the environment.
+    pub(crate) fn env_proxies(mut self, proxies: EnvProxies) -> Self {
+        self.env_proxies = Some(proxies);
+        self.proxy = None;
+        self
+    }
+
+    /// Sets the default maximum time no bytes may be transferred once a
+    /// connection has been established.
+    pub fn read_timeout(mut self, duration: Duration) -> Self {
+        self.read_timeout = Some(duration);
+        self
+    }
+
+    /// Sets the default policy failed requests are retried with.
+    ///
+    /// See [`RetryPolicy`](struct.RetryPolicy.html) for more information.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Sets the default maximum time a request is allowed to take.
+    pub fn timeout(mut self, duration: Duration) -> Self {
+        self.timeout = Some(duration);
+        self
+    }
+
+    /// Sets the default TLS settings for HTTPS requests.
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Gets the `Session` the requests of this client are sent through.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Creates a GET-Request to the specified path.
+    ///
+    /// See [`Client::request`](#method.request) for more information.
+    pub fn get(&self, path: &str) -> Request {
+        self.request(path, Method::Get)
+    }
+
+    /// Creates a DELETE-Request to the specified path.
+    ///
+    /// See [`Client::request`](#method.request) for more information.
+    pub fn delete(&self, path: &str) -> Request {
+        self.request(path, Method::Delete)
+    }
+
+    /// Creates a POST-Request to the specified path.
+    ///
+    /// See [`Client::request`](#method.request) for more information.
+    pub fn post(&self, path: &str) -> Request {
+        self.request(path, Method::Post)
+    }
+
+    /// Creates a PUT-Request to the specified path.
+    ///
+    /// See [`Client::request`](#method.request) for more information.
+    pub fn put(&self, path: &str) -> Request {
+        self.request(path, Method::Put)
+    }
+
+    /// Creates a request with the specified method to the specified path,
+    /// carrying the defaults of this client.
+    ///
+    /// The path is resolved against the base URL, if there is one. Absolute
+    /// URLs are used as they are.
+    ///
+    /// ## Panics
+    /// Panics if the path cannot be resolved to a valid URL.
+    pub fn request(&self, path: &str, method: Method) -> Request {
+        let url = match self.base_url {
+            Some(ref base) => base.join(path),
+            None => Url::parse(path)
+        }.expect("Failed to resolve the request path to a URL.");
+
+        let (bytes, per_duration) = self.lowspeed_limits.unwrap_or((0, Duration::from_secs(0)));
+        let request = Request::new(&url, method)
+            .defaults(&self.headers, self.invalid_header.as_deref(), &self.params)
+            .follow_redirects(self.follow_redirects)
+            .lowspeed_limit(bytes, per_duration)
+            .max_redirects(self.max_redirects)
+            .use_pool(&self.pool);
+        let request = match (self.proxy.as_ref(), self.env_proxies.as_ref()) {
+            (Some(proxy), _) => request.proxy(proxy.clone()),
+            (None, Some(proxies)) => match proxies.for_url(&url) {
+                Some(proxy) => request.proxy(proxy.clone()),
+                None => request.without_proxy()
+            },
+            (None, None) => request
+        };
+        let request = match self.connect_timeout {
+            Some(timeout) => request.connect_timeout(timeout),
+            None => request
+        };
+        let request = match self.cookie_jar {
+            Some(ref jar) => request.use_cookie_jar(jar),
+            None => request
+        };
+        let request = match self.dns_timeout {
+            Some(timeout) => request.dns_timeout(timeout),
+            None => request
+        };
+        let request = match self.read_timeout {
+            Some(timeout) => request.read_timeout(timeout),
+            None => request
+        };
+        let request = match self.retry {
+            Some(ref policy) => request.retry(policy.clone()),
+            None => request
+        };
+        let request = match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request
+        };
+        match self.tls {
+            Some(ref tls) => request.tls(tls.clone()),
+            None => request
+        }
+    }
+
+    /// Downloads the response to the given request to the file at the given
+    /// path, through the session of this client.
+    ///
+    /// See [`Request::download_to_with_session`](struct.Request.html#method.download_to_with_session)
+    /// for more information.
+    pub fn download_to<P: AsRef<Path>>(&self, request: Request, path: P) -> BoxFuture<Download, Error> {
+        request.download_to_with_session(path, &self.session)
+    }
+
+    /// Sends the given request through the session of this client, retrying
+    /// it according to its retry policy.
+    ///
+    /// See [`Request::send`](struct.Request.html#method.send) for more information.
+    pub fn send(&self, request: Request) -> ResponseFuture {
+        request.send_with_retries(&self.session, &self.remote)
+    }
+
+    /// Sends the given request through the session of this client without
+    /// buffering the response body.
+    ///
+    /// See [`Request::send_streaming_with_session`](struct.Request.html#method.send_streaming_with_session)
+    /// for more information.
+    pub fn send_streaming(&self, request: Request) -> SendStreaming {
+        request.send_streaming_with_session(&self.session)
+    }
+}
+
+impl Debug for Client {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(Client))
+            .field("base_url", &self.base_url)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("cookie_jar", &self.cookie_jar)
+            .field("dns_timeout", &self.dns_timeout)
+            .field("env_proxies", &self.env_proxies)
+            .field("follow_redirects", &self.follow_redirects)
+            .field("headers", &auth::redact_headers(&self.headers))
+            .field("invalid_header", &self.invalid_header)
+            .field("lowspeed_limits", &self.lowspeed_limits)
+            .field("max_redirects", &self.max_redirects)
+            .field("params", &self.params)
+            .field("pool", &self.pool)
+            .field("proxy", &self.proxy)
+            .field("read_timeout", &self.read_timeout)
+            .field("retry", &self.retry)
+            .field("timeout", &self.timeout)
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_core::reactor::Core;
+    use url::Url;
+
+    use error::Error;
+    use pool::HandlePool;
+    use test_server::{respond, serve};
+    use super::Client;
+
+    #[test]
+    fn inherits_and_overrides_defaults() {
+        let url = serve(|request, stream| {
+            let body = format!("{} {} {}",
+                               request.path,
+                               request.header("X-Default").unwrap_or("-"),
+                               request.header("X-Override").unwrap_or("-"));
+            respond(stream, "200 OK", &[], body.as_bytes());
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let client = Client::new(evloop.handle())
+            .base_url(&url.join("api/").unwrap())
+            .header("X-Default", "client")
+            .header("x-override", "client")
+            .param("key", "default")
+            .param("other", "default")
+            .timeout(Duration::from_secs(5));
+
+        let request = client.get("users")
+            .header("X-Override", "request")
+            .param("other", "request");
+        let response = evloop.run(client.send(request)).unwrap();
+        assert_eq!(response.body_str(), Some("/api/users?key=default&other=request client request"));
+
+        let absolute = Url::parse(&format!("{}absolute", url)).unwrap();
+        let response = evloop.run(client.send(client.get(absolute.as_str()))).unwrap();
+        assert_eq!(response.body_str(), Some("/absolute?key=default&other=default client client"));
+
+        let request = client.get("users").without_header("x-default");
+        let response = evloop.run(client.send(request)).unwrap();
+        assert_eq!(response.body_str(), Some("/api/users?key=default&other=default - client"));
+    }
+
+    #[test]
+    fn invalid_default_header_fails_requests() {
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], b""));
+        let mut evloop = Core::new().unwrap();
+        let client = Client::new(evloop.handle())
+            .base_url(&url)
+            .header("X-Bad", "line\r\nbreak");
+        match evloop.run(client.send(client.get("path"))) {
+            Err(Error::InvalidHeader(name)) => assert_eq!(name, "X-Bad"),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn reuses_pooled_handles_and_connections() {
+        let url = serve(|_, stream| {
+            let peer = stream.peer_addr().unwrap().to_string();
+            respond(stream, "200 OK", &[], peer.as_bytes());
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let pool = HandlePool::new(2, Duration::from_secs(60));
+        let client = Client::new(evloop.handle())
+            .base_url(&url)
+            .pool(pool.clone());
+
+        let first = evloop.run(client.send(client.get("first"))).unwrap();
+        let first_peer = first.body_str().unwrap().to_owned();
+        assert_eq!(pool.idle(), 0);
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+
+        let second = evloop.run(client.send(client.get("second"))).unwrap();
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(second.body_str(), Some(&*first_peer));
+    }
+}
@@ -0,0 +1,431 @@
+//! The module that contains the cookie jar.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use curl::easy::Easy;
+use error::Error;
+use url::Url;
+
+/// The prefix cURL marks HttpOnly cookies with in cookie files.
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// An HTTP cookie.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cookie {
+    domain: String,
+    expires: Option<SystemTime>,
+    http_only: bool,
+    include_subdomains: bool,
+    name: String,
+    path: String,
+    secure: bool,
+    value: String
+}
+
+/// A jar of cookies that is shared between requests.
+///
+/// Requests using the jar send the cookies matching their URL and store the
+/// cookies the server sets. cURL applies the domain, path, expiry and
+/// `Secure` rules, including on every hop of a redirect. `HttpOnly` cookies
+/// are sent like all other cookies, since every request made through this
+/// library is an HTTP request, and the flag is kept when saving the jar.
+///
+/// The jar can be loaded from and saved to cookie files in the Netscape
+/// format cURL and most browsers understand.
+///
+/// Cloning a jar yields a reference to the same jar.
+///
+/// ```rust,ignore
+/// let jar = CookieJar::load("cookies.txt").unwrap_or_default();
+/// let client = Client::new(evloop.handle()).cookie_jar(jar.clone());
+/// // ...
+/// jar.save("cookies.txt").unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>
+}
+
+impl Cookie {
+    /// Creates a new session cookie that is only sent to the given host and
+    /// the paths below the given path.
+    pub fn new(name: &str, value: &str, domain: &str, path: &str) -> Self {
+        Cookie {
+            domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+            expires: None,
+            http_only: false,
+            include_subdomains: false,
+            name: name.to_owned(),
+            path: path.to_owned(),
+            secure: false,
+            value: value.to_owned()
+        }
+    }
+
+    /// Makes the cookie expire at the given time.
+    pub fn expiring_at(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Marks the cookie as `HttpOnly`.
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    /// Makes the cookie also be sent to the subdomains of its domain.
+    pub fn include_subdomains(mut self) -> Self {
+        self.include_subdomains = true;
+        self
+    }
+
+    /// Marks the cookie as `Secure`, so it is only sent over HTTPS.
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Gets the domain of the cookie.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Gets the time the cookie expires at, or `None` for session cookies.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    /// Checks whether the cookie has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= SystemTime::now())
+    }
+
+    /// Checks whether the cookie is marked as `HttpOnly`.
+    pub fn is_http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// Checks whether the cookie is only sent over HTTPS.
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Checks whether the cookie is also sent to the subdomains of its domain.
+    pub fn includes_subdomains(&self) -> bool {
+        self.include_subdomains
+    }
+
+    /// Gets the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the path of the cookie.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Gets the value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Checks whether the cookie would be sent along with a request to the
+    /// given URL.
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase(),
+            None => return false
+        };
+        let domain_matches = host == self.domain || (self.include_subdomains &&
+            host.len() > self.domain.len() &&
+            host.ends_with(&self.domain) &&
+            host.as_bytes()[host.len() - self.domain.len() - 1] == b'.');
+
+        let path = url.path();
+        let path_matches = path == self.path || (path.starts_with(&self.path) &&
+            (self.path.ends_with('/') || path.as_bytes()[self.path.len()] == b'/'));
+
+        domain_matches && path_matches &&
+            (!self.secure || url.scheme() == "https") &&
+            !self.is_expired()
+    }
+
+    /// Parses a line of a Netscape cookie file.
+    pub(crate) fn from_netscape(line: &str) -> Option<Cookie> {
+        let (line, http_only) = if let Some(line) = line.strip_prefix(HTTP_ONLY_PREFIX) {
+            (line, true)
+        } else if line.starts_with('#') {
+            return None;
+        } else {
+            (line, false)
+        };
+
+        let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        let expires = match fields[4].parse::<u64>().ok()? {
+            0 => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(secs))
+        };
+        let mut cookie = Cookie::new(fields[5], fields[6], fields[0], fields[2]);
+        cookie.expires = expires;
+        cookie.http_only = http_only;
+        cookie.include_subdomains = fields[1].eq_ignore_ascii_case("TRUE");
+        cookie.secure = fields[3].eq_ignore_ascii_case("TRUE");
+        Some(cookie)
+    }
+
+    /// Formats the cookie as a line of a Netscape cookie file.
+    pub(crate) fn to_netscape(&self) -> String {
+        let flag = |flag| if flag { "TRUE" } else { "FALSE" };
+        let expires = self.expires
+            .map(|expires| expires.duration_since(UNIX_EPOCH).map_or(1, |since| since.as_secs().max(1)))
+            .unwrap_or(0);
+        format!("{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if self.http_only { HTTP_ONLY_PREFIX } else { "" },
+                if self.include_subdomains { "." } else { "" },
+                self.domain,
+                flag(self.include_subdomains),
+                self.path,
+                flag(self.secure),
+                expires,
+                self.name,
+                self.value)
+    }
+
+    /// Checks whether both cookies have the same name, domain and path, so
+    /// one replaces the other.
+    fn same_as(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+impl Display for Cookie {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{}={}", self.name, self.value)
+    }
+}
+
+impl CookieJar {
+    /// Creates a new, empty cookie jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a cookie jar from the Netscape cookie file at the given path.
+    ///
+    /// Lines that cannot be parsed are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let jar = Self::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            if let Some(cookie) = Cookie::from_netscape(&line?) {
+                jar.insert(cookie);
+            }
+        }
+        Ok(jar)
+    }
+
+    /// Saves the cookies that have not expired yet to the given path in the
+    /// Netscape cookie file format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "# Netscape HTTP Cookie File")?;
+        for cookie in self.cookies() {
+            writeln!(file, "{}", cookie.to_netscape())?;
+        }
+        file.flush()
+    }
+
+    /// Removes all cookies.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Gets all cookies that have not expired yet.
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired());
+        cookies.clone()
+    }
+
+    /// Gets the cookies that would be sent along with a request to the given URL.
+    pub fn cookies_for(&self, url: &Url) -> Vec<Cookie> {
+        self.cookies().into_iter()
+            .filter(|cookie| cookie.matches(url))
+            .collect()
+    }
+
+    /// Adds the given cookie, replacing the one with the same name, domain
+    /// and path.
+    ///
+    /// Adding an expired cookie removes the one it replaces.
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|other| !other.same_as(&cookie));
+        if !cookie.is_expired() {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Hands the cookies of the jar to the cookie engine of the given handle.
+    ///
+    /// Returns the cookies that were handed over, which are needed to update
+    /// the jar once the transfer is done.
+    pub(crate) fn attach(&self, easy: &mut Easy) -> Result<Vec<Cookie>, Error> {
+        // An empty file name enables the cookie engine without reading a file.
+        easy.cookie_file("")?;
+        let cookies = self.cookies();
+        for cookie in &cookies {
+            easy.cookie_list(&cookie.to_netscape())?;
+        }
+        Ok(cookies)
+    }
+
+    /// Updates the jar with the cookies held by the cookie engine of the given
+    /// handle after a transfer.
+    ///
+    /// Cookies that were handed over but are gone now have been deleted by
+    /// the server.
+    pub(crate) fn collect(&self, easy: &mut Easy, sent: &[Cookie]) {
+        let received: Vec<Cookie> = match easy.cookies() {
+            Ok(list) => list.iter()
+                .filter_map(|line| str::from_utf8(line).ok())
+                .filter_map(Cookie::from_netscape)
+                .collect(),
+            Err(_) => return
+        };
+
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| {
+            !sent.contains(cookie) || received.iter().any(|other| other.same_as(cookie))
+        });
+        for cookie in received {
+            cookies.retain(|other| !other.same_as(&cookie));
+            if !cookie.is_expired() {
+                cookies.push(cookie);
+            }
+        }
+    }
+}
+
+impl Debug for CookieJar {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(CookieJar))
+            .field("cookies", &*self.cookies.lock().unwrap())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use tokio_core::reactor::Core;
+    use url::Url;
+
+    use client::Client;
+    use test_server::{respond, serve, TempPath};
+    use super::{Cookie, CookieJar};
+
+    #[test]
+    fn keeps_cookies_between_requests() {
+        let url = serve(|request, stream| {
+            match request.path.split('?').next().unwrap() {
+                "/login" => respond(stream, "200 OK", &[("Set-Cookie", "session=abc; Path=/; HttpOnly")], b""),
+                "/logout" => respond(stream, "200 OK", &[("Set-Cookie", "session=; Path=/; Max-Age=0")], b""),
+                _ => respond(stream, "200 OK", &[], request.header("Cookie").unwrap_or("-").as_bytes())
+            }
+        });
+
+        let mut evloop = Core::new().unwrap();
+        let jar = CookieJar::new();
+        let client = Client::new(evloop.handle())
+            .base_url(&url)
+            .cookie_jar(jar.clone());
+
+        evloop.run(client.send(client.get("login"))).unwrap();
+        assert_eq!(jar.cookies().len(), 1);
+        assert!(jar.cookies()[0].is_http_only());
+
+        let response = evloop.run(client.send(client.get("me"))).unwrap();
+        assert_eq!(response.body_str(), Some("session=abc"));
+
+        let response = evloop.run(client.send(client.get("me").without_cookie_jar())).unwrap();
+        assert_eq!(response.body_str(), Some("-"));
+
+        evloop.run(client.send(client.get("logout"))).unwrap();
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn saves_and_loads_cookie_files() {
+        let jar = CookieJar::new();
+        let expires = SystemTime::now() + Duration::from_secs(3600);
+        jar.insert(Cookie::new("session", "abc", "example.com", "/").http_only());
+        jar.insert(Cookie::new("pref", "dark", ".example.com", "/app")
+            .include_subdomains()
+            .secure()
+            .expiring_at(expires));
+        jar.insert(Cookie::new("old", "1", "example.com", "/")
+            .expiring_at(SystemTime::now() - Duration::from_secs(1)));
+
+        let path = TempPath::new("cookies.txt");
+        jar.save(&path).unwrap();
+        let loaded = CookieJar::load(&path).unwrap().cookies();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0], jar.cookies()[0]);
+        assert!(loaded[1].includes_subdomains() && loaded[1].is_secure());
+        assert_eq!(loaded[1].domain(), "example.com");
+        assert!(loaded[1].expires().unwrap() <= expires);
+
+        // Saving again replaces the file instead of appending to it.
+        CookieJar::new().save(&path).unwrap();
+        assert!(CookieJar::load(&path).unwrap().cookies().is_empty());
+    }
+
+    #[test]
+    fn loads_cookie_files_written_by_others() {
+        let path = TempPath::new("foreign-cookies.txt");
+        fs::write(&path, "# Netscape HTTP Cookie File\r\n\
+                          \r\n\
+                          #HttpOnly_.example.com\tTRUE\t/\tTRUE\t4102444800\tid\t42\r\n\
+                          example.com\tFALSE\t/\tFALSE\t0\tsession\tabc\r\n\
+                          example.com\tFALSE\t/\tFALSE\tnever\tbad\texpiry\r\n\
+                          example.com\tFALSE\t/\tFALSE\t0\ttoo-short\n\
+                          example.com\tFALSE\t/\tFALSE\t1\tgone\tlong ago\n").unwrap();
+
+        let cookies = CookieJar::load(&path).unwrap().cookies();
+        assert_eq!(cookies.len(), 2);
+        assert!(cookies[0].is_http_only() && cookies[0].includes_subdomains() && cookies[0].is_secure());
+        assert_eq!((cookies[0].domain(), cookies[0].value()), ("example.com", "42"));
+        assert_eq!(cookies[0].expires(), Some(UNIX_EPOCH + Duration::from_secs(4_102_444_800)));
+        assert_eq!((cookies[1].name(), cookies[1].value()), ("session", "abc"));
+        assert_eq!(cookies[1].expires(), None);
+    }
+
+    #[test]
+    fn matches_domain_path_and_scheme() {
+        let host_only = Cookie::new("a", "1", "example.com", "/app");
+        let subdomains = Cookie::new("b", "2", "example.com", "/").include_subdomains().secure();
+        let url = |url| Url::parse(url).unwrap();
+
+        assert!(host_only.matches(&url("http://example.com/app")));
+        assert!(host_only.matches(&url("http://example.com/app/users")));
+        assert!(!host_only.matches(&url("http://example.com/apps")));
+        assert!(!host_only.matches(&url("http://www.example.com/app")));
+
+        assert!(subdomains.matches(&url("https://www.example.com/")));
+        assert!(!subdomains.matches(&url("http://www.example.com/")));
+        assert!(!subdomains.matches(&url("https://badexample.com/")));
+    }
+}
@@ -0,0 +1,142 @@
+//! The module that contains the parser for HTTP dates.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun",
+                            "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+/// Parses a date in one of the three formats HTTP allows.
+///
+/// These are the IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`) and the
+/// obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime
+/// (`Sun Nov  6 08:49:37 1994`) formats.
+pub(crate) fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let (day, month, year, time) = match parts.len() {
+        // IMF-fixdate
+        6 if parts[5] == "GMT" => (parts[1], parts[2], parts[3].parse().ok()?, parts[4]),
+        // RFC 850
+        4 if parts[3] == "GMT" => {
+            let mut date = parts[1].split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+            (day, month, expand_year(year.parse().ok()?, current_year()), parts[2])
+        },
+        // asctime
+        5 => (parts[2], parts[1], parts[4].parse().ok()?, parts[3]),
+        _ => return None
+    };
+
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|&name| name == month)? as u64 + 1;
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if year < 1970 || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let secs = days_since_epoch(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Formats the given time as IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+///
+/// Times before 1970 are formatted as 1970-01-01.
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let days = secs / 86_400;
+    let (year, month, day) = date_from_days(days);
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[(days % 7) as usize],
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            secs % 86_400 / 3600,
+            secs % 3600 / 60,
+            secs % 60)
+}
+
+/// Expands a two-digit year to the latest year ending in those digits that
+/// is at most 50 years after the given one, as RFC 7231 requires.
+fn expand_year(year: u64, current_year: u64) -> u64 {
+    let year = current_year - current_year % 100 + year;
+    if year + 100 <= current_year + 50 {
+        year + 100
+    } else if year > current_year + 50 {
+        year - 100
+    } else {
+        year
+    }
+}
+
+/// Gets the current year.
+fn current_year() -> u64 {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    date_from_days(secs / 86_400).0
+}
+
+/// Counts the days from 1970-01-01 to the given date.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Shift the year to start in March, so the leap day is the last one.
+    let (year, month) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    // 719468 is the day of 1970-01-01 counted from 0000-03-01.
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Gets the year, month and day of the given day counted from 1970-01-01.
+fn date_from_days(days: u64) -> (u64, u64, u64) {
+    // The inverse of `days_since_epoch`.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let (year, month) = if month < 10 { (era * 400 + year_of_era, month + 3) } else { (era * 400 + year_of_era + 1, month - 9) };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{expand_year, format_http_date, parse_http_date};
+
+    #[test]
+    fn parses_all_formats() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+        assert_eq!(parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+                   Some(UNIX_EPOCH + Duration::from_secs(1_709_164_800)));
+    }
+
+    #[test]
+    fn expands_two_digit_years() {
+        assert_eq!(expand_year(94, 2026), 1994);
+        assert_eq!(expand_year(76, 2026), 2076);
+        assert_eq!(expand_year(77, 2026), 1977);
+        assert_eq!(expand_year(5, 2099), 2105);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse_http_date(""), None);
+        assert_eq!(parse_http_date("120"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn formats_imf_fixdate() {
+        for &date in &["Sun, 06 Nov 1994 08:49:37 GMT", "Thu, 29 Feb 2024 00:00:00 GMT", "Thu, 01 Jan 1970 00:00:00 GMT"] {
+            assert_eq!(format_http_date(parse_http_date(date).unwrap()), date);
+        }
+    }
+}
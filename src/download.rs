@@ -0,0 +1,503 @@
+//! The module that contains the code for downloading to files.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use Method;
+
+use curl;
+use curl::easy::Easy;
+use error::{Error, HttpError};
+use futures::{Async, Future, Poll};
+use header::HeaderMap;
+use response::{is_success, read_heads, HeadLines, Response};
+use tokio_curl::Session;
+use transfer::{Prepared, Transfer};
+use url::Url;
+
+/// The summary of a finished download.
+///
+/// See [`Request::download_to`](struct.Request.html#method.download_to).
+pub struct Download {
+    bytes_downloaded: u64,
+    path: PathBuf,
+    response: Response,
+    resumed_from: u64
+}
+
+/// A future that resolves to a [`Download`](struct.Download.html) once the
+/// response body has been written to its file.
+pub(crate) struct DownloadFuture {
+    method: Method,
+    record_redirects: bool,
+    state: Arc<Mutex<State>>,
+    target: Target,
+    transfer: Transfer,
+    url: Url
+}
+
+/// The files a download is written to.
+#[derive(Clone, Debug)]
+pub(crate) struct Target {
+    /// The path of the finished download.
+    pub path: PathBuf,
+    /// The path the body is written to while it is downloaded.
+    pub temp: PathBuf,
+    /// The path of the validator an interrupted download is resumed with.
+    pub validator: PathBuf
+}
+
+/// The state of a transfer whose write callback writes the response body
+/// somewhere.
+pub(crate) trait BodySink: Send + 'static {
+    /// Writes a chunk of the response body.
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Keeps the error that aborted the transfer.
+    fn fail(&mut self, err: io::Error);
+}
+
+/// The state shared between the cURL callbacks and the future.
+struct State {
+    error: Option<io::Error>,
+    file: Option<File>,
+    heads: HeadLines,
+    memory: Vec<u8>,
+    offset: u64,
+    partial: u64,
+    sink_ready: bool,
+    target: Target,
+    written: u64
+}
+
+impl Download {
+    /// Creates the summary of a finished download.
+    pub(crate) fn new(bytes_downloaded: u64, path: PathBuf, response: Response, resumed_from: u64) -> Self {
+        Download {
+            bytes_downloaded,
+            path,
+            response,
+            resumed_from
+        }
+    }
+
+    /// Gets the number of body bytes received by this transfer.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bytes_downloaded
+    }
+
+    /// Consumes the summary and returns the response, whose body is empty.
+    pub fn into_response(self) -> Response {
+        self.response
+    }
+
+    /// Gets the path the body has been written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the response of the final transfer, whose body is empty.
+    pub fn response(&self) -> &Response {
+        &self.response
+    }
+
+    /// Gets the number of bytes that had been downloaded by an earlier,
+    /// interrupted transfer, or 0 if the download started from scratch.
+    pub fn resumed_from(&self) -> u64 {
+        self.resumed_from
+    }
+
+    /// Gets the size of the downloaded file.
+    pub fn total_bytes(&self) -> u64 {
+        self.resumed_from + self.bytes_downloaded
+    }
+}
+
+impl Debug for Download {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(Download))
+            .field("bytes_downloaded", &self.bytes_downloaded)
+            .field("path", &self.path)
+            .field("response", &self.response)
+            .field("resumed_from", &self.resumed_from)
+            .finish()
+    }
+}
+
+impl Target {
+    /// Creates the target for downloading to the given path.
+    ///
+    /// The body is written to `<path>.part` first, next to the validator of
+    /// the response in `<path>.part.validator`.
+    pub fn new(path: &Path) -> Self {
+        let with_suffix = |suffix: &str| {
+            let mut name = path.file_name().map(|name| name.to_owned()).unwrap_or_default();
+            name.push(suffix);
+            path.with_file_name(name)
+        };
+        Target {
+            path: path.to_owned(),
+            temp: with_suffix(".part"),
+            validator: with_suffix(".part.validator")
+        }
+    }
+
+    /// Gets the size of an interrupted download and the validator to resume
+    /// it with, if it can be resumed.
+    pub fn partial(&self) -> Option<(u64, String)> {
+        let len = fs::metadata(&self.temp).ok()?.len();
+        let validator = fs::read_to_string(&self.validator).ok()?;
+        let validator = validator.trim();
+        if len > 0 && !validator.is_empty() {
+            Some((len, validator.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    /// Moves the finished download to its final path.
+    pub fn complete(&self) -> io::Result<()> {
+        fs::rename(&self.temp, &self.path)?;
+        remove_if_exists(&self.validator)
+    }
+}
+
+impl State {
+    /// Decides where the body of the final response goes, once it starts.
+    ///
+    /// Successful bodies go to the temporary file, which is appended to if
+    /// the server resumed the interrupted download. Other bodies are kept
+    /// in memory for the error.
+    fn open_sink(&mut self) -> io::Result<()> {
+        self.sink_ready = true;
+        if !is_success(self.heads.status_code) {
+            return Ok(());
+        }
+
+        let headers = self.heads.headers();
+        if self.heads.status_code == 206 && self.partial > 0 {
+            if content_range_start(&headers) != Some(self.partial) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "server resumed the download at the wrong offset"));
+            }
+            self.file = Some(OpenOptions::new().append(true).open(&self.target.temp)?);
+            self.offset = self.partial;
+        } else {
+            self.file = Some(File::create(&self.target.temp)?);
+            match validator(&headers) {
+                Some(validator) => fs::write(&self.target.validator, validator)?,
+                None => remove_if_exists(&self.target.validator)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BodySink for State {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if !self.sink_ready {
+            self.open_sink()?;
+        }
+        match self.file {
+            Some(ref mut file) => file.write_all(data)?,
+            None => self.memory.extend_from_slice(data)
+        }
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    fn fail(&mut self, err: io::Error) {
+        self.error = Some(err);
+    }
+}
+
+impl DownloadFuture {
+    /// Configures the callbacks on the given handle and starts the transfer.
+    pub fn new(mut prepared: Prepared,
+               target: Target,
+               partial: u64,
+               method: Method,
+               url: Url,
+               record_redirects: bool,
+               session: &Session) -> Result<Self, Error> {
+        let state = Arc::new(Mutex::new(State {
+            error: None,
+            file: None,
+            heads: HeadLines::default(),
+            memory: Vec::new(),
+            offset: 0,
+            partial,
+            sink_ready: false,
+            target: target.clone(),
+            written: 0
+        }));
+        let header_state = state.clone();
+        let write_state = state.clone();
+
+        read_heads(&mut prepared.easy, move |line| header_state.lock().unwrap().heads.push(line))?;
+        write_body(&mut prepared.easy, write_state)?;
+
+        Ok(DownloadFuture {
+            method,
+            record_redirects,
+            state,
+            target,
+            transfer: prepared.perform(session),
+            url
+        })
+    }
+
+    /// Finishes the download once the transfer is done.
+    fn finish(&mut self, easy: Easy) -> Result<Download, Error> {
+        let mut state = self.state.lock().unwrap();
+        let success = is_success(state.heads.status_code);
+        if success && !state.sink_ready {
+            // The body is empty, so the write callback was never called.
+            state.open_sink()?;
+        }
+        if let Some(file) = state.file.take() {
+            file.sync_all()?;
+        }
+
+        // The interrupted download was complete already.
+        let complete = state.heads.status_code == 416 &&
+                       state.partial > 0 &&
+                       content_range_len(&state.heads.headers()) == Some(state.partial);
+        let headers = mem::take(&mut state.heads.lines);
+        let body = mem::take(&mut state.memory);
+        let response = Response::new(easy, headers, body)
+            .keep_redirects(self.record_redirects)
+            .recycle_into(self.transfer.pool().cloned())
+            .sent_to(self.url.clone());
+        if complete {
+            self.target.complete()?;
+            return Ok(Download::new(0, self.target.path.clone(), response, state.partial));
+        }
+        if !success {
+            let err = HttpError::new(self.method.clone(), self.url.clone(), &response);
+            return Err(Error::Http(Box::new(err)));
+        }
+
+        self.target.complete()?;
+        Ok(Download::new(state.written, self.target.path.clone(), response, state.offset))
+    }
+}
+
+impl Future for DownloadFuture {
+    type Item = Download;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Download, Error> {
+        match self.transfer.poll() {
+            Ok(Async::Ready(easy)) => self.finish(easy).map(Async::Ready),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // Prefer the error that made the write callback abort the transfer.
+            Err(err) => match self.state.lock().unwrap().error.take() {
+                Some(err) => Err(Error::Io(err)),
+                None => Err(err)
+            }
+        }
+    }
+}
+
+/// Configures the given handle to write the response body into the given
+/// sink.
+pub(crate) fn write_body<S: BodySink>(easy: &mut Easy, sink: Arc<Mutex<S>>) -> Result<(), curl::Error> {
+    easy.write_function(move |data| {
+        let mut sink = sink.lock().unwrap();
+        match sink.write(data) {
+            Ok(()) => Ok(data.len()),
+            Err(err) => {
+                // Returning less than was given aborts the transfer.
+                sink.fail(err);
+                Ok(0)
+            }
+        }
+    })
+}
+
+/// Gets the first byte position of the `Content-Range` header of a partial
+/// response, as in `bytes 100-199/200`.
+pub(crate) fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get("Content-Range")?.trim();
+    let range = range.strip_prefix("bytes")?.trim_start();
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Gets the complete length from the `Content-Range` header of a response,
+/// as in `bytes 100-199/200` or `bytes */200`.
+fn content_range_len(headers: &HeaderMap) -> Option<u64> {
+    headers.get("Content-Range")?.rsplit('/').next()?.trim().parse().ok()
+}
+
+/// Gets the validator an interrupted download of the given response can be
+/// resumed with.
+///
+/// `If-Range` only allows strong entity tags.
+pub(crate) fn validator(headers: &HeaderMap) -> Option<&str> {
+    headers.get("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| headers.get("Last-Modified"))
+}
+
+/// Removes the file at the given path, if there is one.
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve, TempPath};
+    use super::Target;
+
+    const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// Returns the URL of a server that serves `CONTENT` with a strong
+    /// entity tag and honors ranges if the tag still matches.
+    fn range_server() -> ::url::Url {
+        serve(|request, stream| {
+            if request.path.starts_with("/missing") {
+                return respond(stream, "404 Not Found", &[], b"no such file");
+            }
+            let start = request.header("Range")
+                .filter(|_| request.header("If-Range") == Some("\"v1\""))
+                .and_then(|range| range.trim_start_matches("bytes=").trim_end_matches('-').parse::<usize>().ok());
+            match start {
+                Some(start) if start >= CONTENT.len() => {
+                    let range = format!("bytes */{}", CONTENT.len());
+                    respond(stream, "416 Range Not Satisfiable", &[("ETag", "\"v1\""), ("Content-Range", &range)], b"")
+                },
+                Some(start) => {
+                    let range = format!("bytes {}-{}/{}", start, CONTENT.len() - 1, CONTENT.len());
+                    respond(stream, "206 Partial Content", &[("ETag", "\"v1\""), ("Content-Range", &range)], &CONTENT[start..])
+                },
+                None => respond(stream, "200 OK", &[("ETag", "\"v1\"")], CONTENT)
+            }
+        })
+    }
+
+    #[test]
+    fn downloads_to_file() {
+        let url = range_server();
+        let path = TempPath::new("download");
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.resumed_from(), 0);
+        assert_eq!(download.bytes_downloaded(), CONTENT.len() as u64);
+        assert_eq!(download.response().status_code(), 200);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        assert!(!Target::new(&path).temp.exists());
+        assert!(!Target::new(&path).validator.exists());
+    }
+
+    #[test]
+    fn resumes_interrupted_download() {
+        let url = range_server();
+        let path = TempPath::new("download-resume");
+        let target = Target::new(&path);
+        let mut evloop = Core::new().unwrap();
+
+        fs::write(&target.temp, &CONTENT[..10]).unwrap();
+        fs::write(&target.validator, "\"v1\"").unwrap();
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.resumed_from(), 10);
+        assert_eq!(download.total_bytes(), CONTENT.len() as u64);
+        assert_eq!(download.response().status_code(), 206);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+
+        // A changed resource is downloaded from scratch.
+        fs::write(&target.temp, b"stale").unwrap();
+        fs::write(&target.validator, "\"v0\"").unwrap();
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.resumed_from(), 0);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn finishes_complete_partial_download() {
+        let url = range_server();
+        let path = TempPath::new("download-complete");
+        let target = Target::new(&path);
+        let mut evloop = Core::new().unwrap();
+
+        fs::write(&target.temp, CONTENT).unwrap();
+        fs::write(&target.validator, "\"v1\"").unwrap();
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.response().status_code(), 416);
+        assert_eq!(download.resumed_from(), CONTENT.len() as u64);
+        assert_eq!(download.bytes_downloaded(), 0);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        assert!(!target.temp.exists());
+        assert!(!target.validator.exists());
+
+        // A partial download that is longer than the resource is not complete.
+        fs::write(&target.temp, [CONTENT, b"junk"].concat()).unwrap();
+        fs::write(&target.validator, "\"v1\"").unwrap();
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Http(err)) => assert_eq!(err.status().0, 416),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn restarts_download_without_validator() {
+        let ranges = Arc::new(AtomicUsize::new(0));
+        let counter = ranges.clone();
+        let url = serve(move |request, stream| {
+            if request.header("Range").is_some() {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            respond(stream, "200 OK", &[], CONTENT)
+        });
+        let path = TempPath::new("download-restart");
+        let target = Target::new(&path);
+        let mut evloop = Core::new().unwrap();
+
+        // Neither the partial download nor the old file are kept.
+        fs::write(&path, b"old").unwrap();
+        fs::write(&target.temp, b"partial").unwrap();
+        let future = get(url.as_str()).download_to(&path, evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(ranges.load(Ordering::SeqCst), 0);
+        assert_eq!(download.resumed_from(), 0);
+        assert_eq!(fs::read(&path).unwrap(), CONTENT);
+        assert!(!target.validator.exists());
+    }
+
+    #[test]
+    fn keeps_partial_download_on_error() {
+        let url = range_server();
+        let path = TempPath::new("download-missing");
+        let target = Target::new(&path);
+        let mut evloop = Core::new().unwrap();
+
+        fs::write(&target.temp, b"partial").unwrap();
+        let future = get(url.join("missing").unwrap().as_str()).download_to(&path, evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Http(err)) => assert_eq!(err.body_preview(), "no such file"),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert_eq!(fs::read(&target.temp).unwrap(), b"partial");
+        assert!(!path.exists());
+    }
+}
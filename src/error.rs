@@ -0,0 +1,471 @@
+//! The module that contains the error type.
+
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+
+use Method;
+
+use curl;
+use curl::easy::Easy;
+use curl_sys;
+use header::HeaderMap;
+use response::Response;
+use segmented::Checksum;
+use status::StatusCode;
+use timeout::Timeouts;
+use url::Url;
+
+/// The maximum number of body bytes kept in an [`HttpError`](struct.HttpError.html).
+const BODY_PREVIEW_LENGTH: usize = 512;
+
+/// Represents an error that occured while sending a request.
+///
+/// Variants that are caused by cURL keep the underlying `curl::Error`,
+/// so the exact cURL error code can still be inspected.
+#[derive(Debug)]
+pub enum Error {
+    /// The response body could not be decoded according to its
+    /// `Content-Encoding`.
+    BodyEncoding(curl::Error),
+    /// The request was cancelled through an
+    /// [`AbortHandle`](struct.AbortHandle.html).
+    Cancelled,
+    /// The downloaded file does not have the expected checksum.
+    ///
+    /// See [`SegmentedDownload::checksum`](struct.SegmentedDownload.html#method.checksum).
+    ChecksumMismatch {
+        /// The checksum of the downloaded file.
+        actual: Checksum,
+        /// The checksum the file was expected to have.
+        expected: Checksum
+    },
+    /// The connection to the server could not be established.
+    Connect(curl::Error),
+    /// The connection to the server could not be established in time.
+    ///
+    /// See [`Request::connect_timeout`](struct.Request.html#method.connect_timeout).
+    ConnectTimeout(curl::Error),
+    /// Any other error reported by cURL.
+    Curl(curl::Error),
+    /// The deadline of the request passed before it completed.
+    ///
+    /// See [`Request::deadline`](struct.Request.html#method.deadline).
+    Deadline(curl::Error),
+    /// The host name could not be resolved in time.
+    ///
+    /// See [`Request::dns_timeout`](struct.Request.html#method.dns_timeout).
+    DnsTimeout(curl::Error),
+    /// The server answered with a non-successful status code.
+    ///
+    /// See [`ResponseFuture::error_for_status`](struct.ResponseFuture.html#method.error_for_status).
+    Http(Box<HttpError>),
+    /// The form data could not be serialized, for the given reason.
+    ///
+    /// See [`Request::form_serialize`](struct.Request.html#method.form_serialize).
+    InvalidForm(String),
+    /// The header with the given name could not be added to the request.
+    InvalidHeader(String),
+    /// The proxy has a scheme that is not supported.
+    ///
+    /// See [`Proxy`](struct.Proxy.html).
+    InvalidProxy(String),
+    /// An I/O error, e.g. while reading the request body.
+    Io(io::Error),
+    /// The transfer was aborted because it was slower than the low speed
+    /// limit for too long.
+    ///
+    /// See [`Request::lowspeed_limit`](struct.Request.html#method.lowspeed_limit).
+    LowSpeed(curl::Error),
+    /// No bytes were transferred for longer than the read timeout.
+    ///
+    /// See [`Request::read_timeout`](struct.Request.html#method.read_timeout).
+    ReadTimeout(curl::Error),
+    /// The host name (or the one of the proxy) could not be resolved.
+    Resolve(curl::Error),
+    /// The request took longer than the configured timeout.
+    ///
+    /// See [`Request::timeout`](struct.Request.html#method.timeout).
+    Timeout(curl::Error),
+    /// The TLS handshake failed or the server's certificate could not be
+    /// verified.
+    Tls(curl::Error),
+    /// The server redirected the request more often than allowed.
+    ///
+    /// See [`Request::max_redirects`](struct.Request.html#method.max_redirects).
+    TooManyRedirects(curl::Error)
+}
+
+impl Error {
+    /// Gets the underlying cURL error, if this error was caused by cURL.
+    pub fn curl_error(&self) -> Option<&curl::Error> {
+        match *self {
+            Error::BodyEncoding(ref err) |
+            Error::Connect(ref err) |
+            Error::ConnectTimeout(ref err) |
+            Error::Curl(ref err) |
+            Error::Deadline(ref err) |
+            Error::DnsTimeout(ref err) |
+            Error::LowSpeed(ref err) |
+            Error::ReadTimeout(ref err) |
+            Error::Resolve(ref err) |
+            Error::Timeout(ref err) |
+            Error::Tls(ref err) |
+            Error::TooManyRedirects(ref err) => Some(err),
+            Error::Cancelled |
+            Error::ChecksumMismatch { .. } |
+            Error::Http(_) |
+            Error::InvalidForm(_) |
+            Error::InvalidHeader(_) |
+            Error::InvalidProxy(_) |
+            Error::Io(_) => None
+        }
+    }
+
+    /// Checks whether this is a timeout or low speed error.
+    pub fn is_timeout(&self) -> bool {
+        matches!(*self,
+            Error::ConnectTimeout(_) |
+            Error::Deadline(_) |
+            Error::DnsTimeout(_) |
+            Error::LowSpeed(_) |
+            Error::ReadTimeout(_) |
+            Error::Timeout(_))
+    }
+
+    /// Converts the error of a failed transfer with the given timeouts into
+    /// an `Error`.
+    pub(crate) fn from_transfer(err: io::Error, easy: Option<&Easy>, timeouts: &Timeouts) -> Self {
+        let mut curl_err = match into_curl_error(err) {
+            Ok(err) => err,
+            Err(err) => return Error::Io(err)
+        };
+        if let Some(extra) = easy.and_then(|easy| easy.take_error_buf()) {
+            curl_err.set_extra(extra);
+        }
+        timeouts.classify(curl_err)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match *self {
+            Error::BodyEncoding(ref err) => write!(fmt, "failed to decode the response body: {}", err),
+            Error::Cancelled => write!(fmt, "request was cancelled"),
+            Error::ChecksumMismatch { actual, expected } => {
+                write!(fmt, "checksum mismatch: expected {}, got {}", expected, actual)
+            },
+            Error::Connect(ref err) => write!(fmt, "failed to connect: {}", err),
+            Error::ConnectTimeout(ref err) => write!(fmt, "connecting timed out: {}", err),
+            Error::Curl(ref err) => write!(fmt, "{}", err),
+            Error::Deadline(ref err) => write!(fmt, "deadline exceeded: {}", err),
+            Error::DnsTimeout(ref err) => write!(fmt, "resolving host timed out: {}", err),
+            Error::Http(ref err) => write!(fmt, "{}", err),
+            Error::InvalidForm(ref reason) => write!(fmt, "invalid form data: {}", reason),
+            Error::InvalidHeader(ref name) => write!(fmt, "invalid header '{}'", name),
+            Error::InvalidProxy(ref scheme) => write!(fmt, "unsupported proxy scheme '{}'", scheme),
+            Error::Io(ref err) => write!(fmt, "{}", err),
+            Error::LowSpeed(ref err) => write!(fmt, "transfer too slow: {}", err),
+            Error::ReadTimeout(ref err) => write!(fmt, "transfer stalled: {}", err),
+            Error::Resolve(ref err) => write!(fmt, "failed to resolve host: {}", err),
+            Error::Timeout(ref err) => write!(fmt, "request timed out: {}", err),
+            Error::Tls(ref err) => write!(fmt, "TLS error: {}", err),
+            Error::TooManyRedirects(ref err) => write!(fmt, "too many redirects: {}", err)
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::Http(ref err) => Some(&**err),
+            Error::Io(ref err) => Some(err),
+            _ => self.curl_error().map(|err| err as &(dyn StdError + 'static))
+        }
+    }
+}
+
+impl From<curl::Error> for Error {
+    fn from(err: curl::Error) -> Self {
+        match err.code() {
+            curl_sys::CURLE_COULDNT_RESOLVE_HOST |
+            curl_sys::CURLE_COULDNT_RESOLVE_PROXY => Error::Resolve(err),
+            curl_sys::CURLE_COULDNT_CONNECT => Error::Connect(err),
+            curl_sys::CURLE_OPERATION_TIMEDOUT => Error::Timeout(err),
+            curl_sys::CURLE_TOO_MANY_REDIRECTS => Error::TooManyRedirects(err),
+            curl_sys::CURLE_BAD_CONTENT_ENCODING => Error::BodyEncoding(err),
+            curl_sys::CURLE_SSL_CONNECT_ERROR |
+            curl_sys::CURLE_PEER_FAILED_VERIFICATION |
+            curl_sys::CURLE_SSL_CERTPROBLEM |
+            curl_sys::CURLE_SSL_CIPHER |
+            curl_sys::CURLE_SSL_CACERT_BADFILE |
+            curl_sys::CURLE_SSL_CRL_BADFILE |
+            curl_sys::CURLE_SSL_SHUTDOWN_FAILED |
+            curl_sys::CURLE_SSL_ISSUER_ERROR |
+            curl_sys::CURLE_SSL_PINNEDPUBKEYNOTMATCH |
+            curl_sys::CURLE_SSL_INVALIDCERTSTATUS |
+            curl_sys::CURLE_SSL_ENGINE_NOTFOUND |
+            curl_sys::CURLE_SSL_ENGINE_SETFAILED |
+            curl_sys::CURLE_SSL_ENGINE_INITFAILED |
+            curl_sys::CURLE_USE_SSL_FAILED => Error::Tls(err),
+            _ => Error::Curl(err)
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Unwraps cURL errors that have been wrapped into an `io::Error`.
+    fn from(err: io::Error) -> Self {
+        match into_curl_error(err) {
+            Ok(err) => Self::from(err),
+            Err(err) => Error::Io(err)
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            _ if err.is_timeout() => io::Error::new(io::ErrorKind::TimedOut, err),
+            other => io::Error::other(other)
+        }
+    }
+}
+
+/// The details of a response with a non-successful status code.
+///
+/// ```rust,ignore
+/// match evloop.run(get(&url).send(evloop.handle()).error_for_status()) {
+///     Err(Error::Http(err)) => println!("{} {}", err.status(), err.body_preview()),
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct HttpError {
+    body_preview: String,
+    headers: HeaderMap,
+    method: Method,
+    status: StatusCode,
+    url: Url
+}
+
+impl HttpError {
+    /// Creates the error for the given response to a request with the given
+    /// method and URL.
+    pub(crate) fn new(method: Method, url: Url, response: &Response) -> Self {
+        let body = response.body();
+        let preview = &body[..body.len().min(BODY_PREVIEW_LENGTH)];
+        HttpError {
+            body_preview: String::from_utf8_lossy(preview).into_owned(),
+            headers: response.headers().clone(),
+            method,
+            status: response.status(),
+            url
+        }
+    }
+
+    /// Gets the start of the response body, decoded lossily as UTF-8.
+    ///
+    /// At most 512 bytes of the body are kept.
+    pub fn body_preview(&self) -> &str {
+        &self.body_preview
+    }
+
+    /// Gets the response headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Gets the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Gets the response status code.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Gets the URL the request was sent to.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+impl Display for HttpError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{} {} failed with status {}", self.method, self.url, self.status)
+    }
+}
+
+impl StdError for HttpError {}
+
+/// Extracts the `curl::Error` from an `io::Error` created from one.
+fn into_curl_error(err: io::Error) -> Result<curl::Error, io::Error> {
+    if err.get_ref().is_some_and(|inner| inner.is::<curl::Error>()) {
+        let inner = err.into_inner().unwrap().downcast::<curl::Error>().unwrap();
+        Ok(*inner)
+    } else {
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use tokio_core::reactor::Core;
+
+    use retry::RetryPolicy;
+    use str::get;
+    use test_server::{respond, serve};
+    use super::Error;
+
+    /// Returns the URL of a server that accepts requests but never answers.
+    fn silent_server() -> String {
+        serve(|_, _| thread::sleep(Duration::from_secs(10))).into_string()
+    }
+
+    #[test]
+    fn connection_refused() {
+        // Bind and drop a listener to get hold of a port nobody listens on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(&format!("http://127.0.0.1:{}/", port)).send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Connect(err)) => assert!(err.is_couldnt_connect()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn error_for_status() {
+        let url = serve(|request, stream| {
+            if request.path.starts_with("/missing") {
+                respond(stream, "404 Not Found", &[("X-Trace", "abc")], &[b'x'; 1000])
+            } else {
+                respond(stream, "200 OK", &[], b"found")
+            }
+        });
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str()).send(evloop.handle()).error_for_status();
+        assert_eq!(evloop.run(future).unwrap().body_str(), Some("found"));
+
+        let missing = url.join("missing").unwrap();
+        let future = get(missing.as_str()).send(evloop.handle()).error_for_status();
+        match evloop.run(future) {
+            Err(Error::Http(err)) => {
+                assert_eq!(err.status(), 404);
+                assert_eq!(err.headers().get("x-trace"), Some("abc"));
+                assert_eq!(err.body_preview().len(), 512);
+                assert_eq!(err.url(), &missing);
+                assert_eq!(err.to_string(), format!("GET {} failed with status 404 Not Found", missing));
+            },
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let mut evloop = Core::new().unwrap();
+        let future = get(&silent_server())
+            .timeout(Duration::from_millis(500))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Timeout(err)) => assert!(err.is_operation_timedout()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn lowspeed_abort() {
+        let mut evloop = Core::new().unwrap();
+        let future = get(&silent_server())
+            .lowspeed_limit(1, Duration::from_secs(1))
+            .timeout(Duration::from_secs(5))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::LowSpeed(err)) => assert!(err.is_operation_timedout()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn read_timeout() {
+        let url = serve(|_, stream| {
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nxx");
+            thread::sleep(Duration::from_secs(10));
+        });
+        let mut evloop = Core::new().unwrap();
+        let start = Instant::now();
+        let future = get(url.as_str())
+            .lowspeed_limit(0, Duration::from_secs(0))
+            .read_timeout(Duration::from_secs(1))
+            .timeout(Duration::from_secs(5))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::ReadTimeout(err)) => assert!(err.is_aborted_by_callback()),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn read_timeout_allows_slow_transfers() {
+        let url = serve(|_, stream| {
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+            for _ in 0..5 {
+                thread::sleep(Duration::from_millis(400));
+                let _ = stream.write_all(b"x").and_then(|_| stream.flush());
+            }
+        });
+        let mut evloop = Core::new().unwrap();
+        let future = get(url.as_str())
+            .read_timeout(Duration::from_secs(1))
+            .send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().body_str(), Some("xxxxx"));
+    }
+
+    #[test]
+    fn deadline() {
+        let mut evloop = Core::new().unwrap();
+        let future = get(&silent_server())
+            .deadline(Instant::now() + Duration::from_millis(500))
+            .timeout(Duration::from_secs(5))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Deadline(err)) => assert!(err.is_operation_timedout()),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        let future = get(&silent_server())
+            .deadline(Instant::now())
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Deadline(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn deadline_spans_retries() {
+        let mut evloop = Core::new().unwrap();
+        let start = Instant::now();
+        let future = get(&silent_server())
+            .deadline(start + Duration::from_secs(1))
+            .retry(RetryPolicy::new(10).backoff(Duration::from_millis(50), Duration::from_millis(50)))
+            .timeout(Duration::from_millis(300))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(ref err) if err.is_timeout() => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,188 @@
+//! The module that contains the header map.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+use error::Error;
+
+/// An ordered multimap of HTTP headers.
+///
+/// Header names are matched case-insensitively, but kept the way they were
+/// inserted. The headers are iterated over in the order they were added.
+///
+/// ```rust,ignore
+/// let mut headers = HeaderMap::new();
+/// headers.append("Accept", "text/html").unwrap();
+/// headers.append("accept", "application/json").unwrap();
+/// assert_eq!(headers.get("ACCEPT"), Some("text/html"));
+/// assert_eq!(headers.get_all("Accept").count(), 2);
+/// ```
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>
+}
+
+impl HeaderMap {
+    /// Creates a new, empty header map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header, keeping the existing values with the same name.
+    ///
+    /// Fails with [`Error::InvalidHeader`](enum.Error.html#variant.InvalidHeader)
+    /// if the name is not a valid token or the value contains a line break,
+    /// which could be used to inject headers.
+    pub fn append(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let value = validate(name, value)?;
+        self.entries.push((name.to_owned(), value.to_owned()));
+        Ok(())
+    }
+
+    /// Checks whether there is a header with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Gets the first value of the header with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|kvp| kvp.0.eq_ignore_ascii_case(name))
+            .map(|kvp| kvp.1.as_str())
+    }
+
+    /// Gets all values of the header with the given name, in the order they
+    /// were added.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries.iter()
+            .filter(move |kvp| kvp.0.eq_ignore_ascii_case(name))
+            .map(|kvp| kvp.1.as_str())
+    }
+
+    /// Sets the header with the given name, replacing all existing values.
+    ///
+    /// The header keeps the position of its first value, if there was one.
+    /// Fails like [`HeaderMap::append`](#method.append).
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let value = validate(name, value)?;
+        match self.entries.iter().position(|kvp| kvp.0.eq_ignore_ascii_case(name)) {
+            Some(index) => {
+                let rest = self.entries.split_off(index + 1);
+                self.entries[index] = (name.to_owned(), value.to_owned());
+                self.entries.extend(rest.into_iter().filter(|kvp| !kvp.0.eq_ignore_ascii_case(name)));
+            },
+            None => self.entries.push((name.to_owned(), value.to_owned()))
+        }
+        Ok(())
+    }
+
+    /// Checks whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the names and values of all headers.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|kvp| (kvp.0.as_str(), kvp.1.as_str()))
+    }
+
+    /// Gets the number of header values in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes all values of the header with the given name and returns the
+    /// first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.entries.iter().position(|kvp| kvp.0.eq_ignore_ascii_case(name))?;
+        let (_, value) = self.entries.remove(index);
+        self.entries.retain(|kvp| !kvp.0.eq_ignore_ascii_case(name));
+        Some(value)
+    }
+
+    /// Creates a map from headers that have already been validated.
+    pub(crate) fn from_entries(entries: Vec<(String, String)>) -> Self {
+        HeaderMap { entries }
+    }
+}
+
+impl Debug for HeaderMap {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Checks the given header and returns the value without the surrounding
+/// whitespace.
+pub(crate) fn validate<'a>(name: &str, value: &'a str) -> Result<&'a str, Error> {
+    let value = value.trim_matches(|c| c == ' ' || c == '\t');
+    if is_token(name) && !value.contains(&['\r', '\n', '\0'][..]) {
+        Ok(value)
+    } else {
+        Err(Error::InvalidHeader(name.to_owned()))
+    }
+}
+
+/// Checks whether the given string is a valid header name.
+pub(crate) fn is_token(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve};
+    use super::HeaderMap;
+
+    #[test]
+    fn matches_names_case_insensitively() {
+        let mut headers = HeaderMap::new();
+        headers.append("Set-Cookie", "a=1").unwrap();
+        headers.append("Content-Type", "text/plain").unwrap();
+        headers.append("set-cookie", "b=2").unwrap();
+
+        assert_eq!(headers.get("content-type"), Some("text/plain"));
+        assert_eq!(headers.get_all("SET-COOKIE").collect::<Vec<_>>(), vec!["a=1", "b=2"]);
+
+        headers.insert("SET-COOKIE", "c=3").unwrap();
+        assert_eq!(headers.iter().collect::<Vec<_>>(), vec![("SET-COOKIE", "c=3"), ("Content-Type", "text/plain")]);
+
+        assert_eq!(headers.remove("content-TYPE"), Some("text/plain".to_owned()));
+        assert_eq!(headers.remove("Content-Type"), None);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in &[("X-Test", "a\r\nX-Injected: yes"), ("X-Test\r\nX-Injected", "yes"), ("Bad Name", "a"), ("", "a")] {
+            match headers.append(name, value) {
+                Err(Error::InvalidHeader(ref invalid)) if invalid == name => {},
+                other => panic!("unexpected result {:?}", other)
+            }
+        }
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn request_and_response_headers() {
+        let url = serve(|request, stream| {
+            let echo = request.header("X-Echo").unwrap_or("-").to_owned();
+            respond(stream, "200 OK", &[("content-type", "text/plain"), ("x-echo", &echo)], b"")
+        });
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str()).header("x-echo", "hello").send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.content_type().map(|mime| mime.to_string()), Some("text/plain".to_owned()));
+        assert_eq!(response.header("X-Echo"), Some("hello"));
+
+        let future = get(url.as_str()).header("X-Echo", "a\r\nX-Injected: yes").send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::InvalidHeader(name)) => assert_eq!(name, "X-Echo"),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
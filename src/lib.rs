@@ -54,21 +54,24 @@
 //!
 //! # Caveats
 //! Right now the focus for this library is on interacting with REST
-//! APIs that talk JSON, so this library is buffering the entire response
-//! into memory. This means it is not recommended for downloading large
-//! files from the internet. Streaming request / response bodies will be
-//! added at a later stage when implementation and API details have been
-//! figured out.
+//! APIs that talk JSON, so `Request::send` is buffering the entire response
+//! into memory. For downloading large files from the internet, use
+//! `Request::send_streaming` instead, which hands out the response body
+//! chunk by chunk.
 
 #![deny(missing_docs)]
 
+extern crate bytes;
 extern crate curl;
+extern crate curl_sys;
 extern crate futures;
 extern crate mime;
 extern crate tokio_core;
 extern crate tokio_curl;
 extern crate url;
 
+#[cfg(test)]
+extern crate openssl;
 #[cfg(feature = "rustc-serialization")]
 extern crate rustc_serialize;
 
@@ -77,15 +80,63 @@ extern crate serde;
 #[cfg(feature = "serde-serialization")]
 extern crate serde_json;
 
+mod abort;
+mod auth;
+mod body;
+mod client;
+mod cookie;
+mod date;
+mod download;
+mod error;
+mod header;
+mod multipart;
+mod pool;
+mod progress;
+mod proxy;
+mod random;
 mod request;
 mod response;
+mod retry;
+mod segmented;
+mod status;
+mod stream;
+mod timeout;
+mod timings;
+mod tls;
+mod transfer;
+mod typed_headers;
+
+#[cfg(test)]
+mod test_server;
 
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
+use futures::Future;
 use url::Url;
 
+pub use self::abort::*;
+pub use self::client::*;
+pub use self::cookie::*;
+pub use self::download::*;
+pub use self::error::*;
+pub use self::header::*;
+pub use self::multipart::*;
+pub use self::pool::*;
+pub use self::progress::*;
+pub use self::proxy::*;
 pub use self::request::*;
 pub use self::response::*;
+pub use self::retry::*;
+pub use self::segmented::*;
+pub use self::status::*;
+pub use self::stream::*;
+pub use self::timings::*;
+pub use self::tls::*;
+pub use self::typed_headers::*;
+
+/// A boxed, sendable future as returned by
+/// [`Request::send`](struct.Request.html#method.send).
+pub type BoxFuture<T, E> = Box<dyn Future<Item = T, Error = E> + Send>;
 
 /// Issue a GET-Request to the specified URL.
 pub fn get(url: &Url) -> Request {
@@ -145,9 +196,10 @@ pub mod str {
 }
 
 /// Represents an HTTP method.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub enum Method {
     /// GET
+    #[default]
     Get,
     /// POST
     Post,
@@ -169,6 +221,20 @@ pub enum Method {
     Custom(String)
 }
 
+impl Method {
+    /// Checks whether sending a request with this method more than once has
+    /// the same effect as sending it once.
+    ///
+    /// Custom methods are assumed not to be idempotent.
+    pub fn is_idempotent(&self) -> bool {
+        match *self {
+            Method::Delete | Method::Get | Method::Head |
+            Method::Options | Method::Put | Method::Trace => true,
+            Method::Connect | Method::Custom(_) | Method::Patch | Method::Post => false
+        }
+    }
+}
+
 impl AsRef<str> for Method {
     fn as_ref(&self) -> &str {
         match *self {
@@ -186,12 +252,6 @@ impl AsRef<str> for Method {
     }
 }
 
-impl Default for Method {
-    fn default() -> Self {
-        Method::Get
-    }
-}
-
 impl Display for Method {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
         fmt.write_str(self.as_ref())
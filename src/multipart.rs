@@ -0,0 +1,458 @@
+//! The module that contains the multipart/form-data body builder.
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use error::Error;
+use header::HeaderMap;
+use mime::{Mime, SubLevel, TopLevel};
+use random::random_u64;
+
+/// A multipart/form-data request body.
+///
+/// The parts are sent in the order they were added in. Files are streamed
+/// from disk while the request is sent instead of being loaded into memory.
+///
+/// ```rust,ignore
+/// let form = Multipart::new()
+///                .text("name", "Jane Doe")
+///                .file("avatar", "/tmp/avatar.png");
+/// let future = post("https://httpbin.org/post").multipart(form).send(evloop.handle());
+/// ```
+#[derive(Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<(String, Part)>
+}
+
+/// A single part of a [`Multipart`](struct.Multipart.html) body.
+#[derive(Clone)]
+pub struct Part {
+    content_type: Option<Mime>,
+    file_name: Option<String>,
+    headers: HeaderMap,
+    invalid_header: Option<String>,
+    source: Source
+}
+
+/// Where the content of a part comes from.
+#[derive(Clone)]
+enum Source {
+    Bytes(Vec<u8>),
+    File(PathBuf)
+}
+
+/// Reads a multipart body, part by part.
+pub(crate) struct MultipartReader {
+    current: usize,
+    segments: Vec<(Segment, u64)>
+}
+
+/// A piece of a multipart body.
+enum Segment {
+    Bytes(Cursor<Vec<u8>>),
+    File(File)
+}
+
+impl Multipart {
+    /// Creates a new, empty multipart body with a randomly generated boundary.
+    pub fn new() -> Self {
+        Multipart {
+            boundary: generate_boundary(),
+            parts: Vec::new()
+        }
+    }
+
+    /// Gets the boundary separating the parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Adds a part with the given in-memory content.
+    ///
+    /// See [`Part::bytes`](struct.Part.html#method.bytes) for more information.
+    pub fn bytes<B: Into<Vec<u8>>>(self, name: &str, data: B) -> Self {
+        self.part(name, Part::bytes(data))
+    }
+
+    /// Adds a part with the content of the file at the given path.
+    ///
+    /// See [`Part::file`](struct.Part.html#method.file) for more information.
+    pub fn file<P: AsRef<Path>>(self, name: &str, path: P) -> Self {
+        self.part(name, Part::file(path))
+    }
+
+    /// Adds the given part under the given name.
+    pub fn part(mut self, name: &str, part: Part) -> Self {
+        self.parts.push((name.to_owned(), part));
+        self
+    }
+
+    /// Adds a text field.
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.part(name, Part::text(value))
+    }
+
+    /// Gets the value of the `Content-Type` header for this body.
+    pub(crate) fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Gets the length of the body, if the files can be inspected.
+    pub(crate) fn len(&self) -> Option<u64> {
+        self.segments(|path| path.metadata().map(|meta| meta.len()))
+            .ok()
+            .map(|(_, len)| len)
+    }
+
+    /// Opens all files and turns the body into a reader, along with its length.
+    ///
+    /// Fails if a part has an invalid header.
+    pub(crate) fn into_reader(self) -> Result<(MultipartReader, u64), Error> {
+        if let Some(name) = self.parts.iter().find_map(|(_, part)| part.invalid_header.as_ref()) {
+            return Err(Error::InvalidHeader(name.clone()));
+        }
+        let mut files = VecDeque::new();
+        let (segments, len) = self.segments(|path| {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+            files.push_back((file, len));
+            Ok(len)
+        })?;
+
+        let segments = segments.into_iter()
+            .map(|segment| match segment {
+                Some(bytes) => {
+                    let len = bytes.len() as u64;
+                    (Segment::Bytes(Cursor::new(bytes)), len)
+                },
+                None => {
+                    let (file, len) = files.pop_front().unwrap();
+                    (Segment::File(file), len)
+                }
+            })
+            .collect();
+        let reader = MultipartReader {
+            current: 0,
+            segments
+        };
+        Ok((reader, len))
+    }
+
+    /// Renders the body into its segments, where file contents are left
+    /// out as `None`, and computes the total length.
+    ///
+    /// `file_len` is called for every file in order.
+    fn segments<F>(&self, mut file_len: F) -> io::Result<(Vec<Option<Vec<u8>>>, u64)>
+            where F: FnMut(&Path) -> io::Result<u64> {
+        let mut segments = Vec::new();
+        let mut len = 0;
+        for (name, part) in &self.parts {
+            let mut head = format!("--{}\r\n", self.boundary).into_bytes();
+            part.write_headers(name, &mut head);
+            match part.source {
+                Source::Bytes(ref data) => {
+                    head.extend_from_slice(data);
+                    head.extend_from_slice(b"\r\n");
+                    segments.push(Some(head));
+                },
+                Source::File(ref path) => {
+                    segments.push(Some(head));
+                    len += file_len(path)?;
+                    segments.push(None);
+                    segments.push(Some(b"\r\n".to_vec()));
+                }
+            }
+        }
+        segments.push(Some(format!("--{}--\r\n", self.boundary).into_bytes()));
+        len += segments.iter().flatten().map(|bytes| bytes.len() as u64).sum::<u64>();
+
+        Ok((segments, len))
+    }
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Multipart {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(Multipart))
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts)
+            .finish()
+    }
+}
+
+impl Part {
+    /// Creates a part with the given in-memory content.
+    ///
+    /// The content type defaults to `application/octet-stream`.
+    pub fn bytes<B: Into<Vec<u8>>>(data: B) -> Self {
+        Part {
+            content_type: Some(Mime(TopLevel::Application, SubLevel::OctetStream, Vec::new())),
+            file_name: None,
+            headers: HeaderMap::new(),
+            invalid_header: None,
+            source: Source::Bytes(data.into())
+        }
+    }
+
+    /// Creates a part that is streamed from the file at the given path.
+    ///
+    /// The file is opened when the request is sent. The file name defaults
+    /// to the last component of the path and the content type defaults to
+    /// `application/octet-stream`.
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        Part {
+            content_type: Some(Mime(TopLevel::Application, SubLevel::OctetStream, Vec::new())),
+            file_name: path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            headers: HeaderMap::new(),
+            invalid_header: None,
+            source: Source::File(path.to_owned())
+        }
+    }
+
+    /// Creates a text part.
+    ///
+    /// Text parts have no content type, which means `text/plain`.
+    pub fn text(value: &str) -> Self {
+        Part {
+            content_type: None,
+            file_name: None,
+            headers: HeaderMap::new(),
+            invalid_header: None,
+            source: Source::Bytes(value.as_bytes().to_vec())
+        }
+    }
+
+    /// Sets the content type of the part.
+    pub fn content_type(mut self, content_type: Mime) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// Sets the file name the part is sent with.
+    pub fn file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_owned());
+        self
+    }
+
+    /// Adds a header to the part.
+    ///
+    /// An invalid header makes sending the request fail with
+    /// [`Error::InvalidHeader`](enum.Error.html#variant.InvalidHeader), see
+    /// [`HeaderMap::append`](struct.HeaderMap.html#method.append).
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if self.headers.append(name, value).is_err() && self.invalid_header.is_none() {
+            self.invalid_header = Some(name.to_owned());
+        }
+        self
+    }
+
+    /// Writes the headers of the part including the blank line after them.
+    fn write_headers(&self, name: &str, buf: &mut Vec<u8>) {
+        let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", escape(name));
+        if let Some(ref file_name) = self.file_name {
+            disposition.push_str(&format!("; filename=\"{}\"", escape(file_name)));
+        }
+        buf.extend_from_slice(disposition.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+
+        if let Some(ref content_type) = self.content_type {
+            buf.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        for (name, value) in self.headers.iter() {
+            buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+impl Debug for Part {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let (len, path) = match self.source {
+            Source::Bytes(ref data) => (Some(data.len()), None),
+            Source::File(ref path) => (None, Some(path))
+        };
+        fmt.debug_struct(stringify!(Part))
+            .field("content_type", &self.content_type)
+            .field("file_name", &self.file_name)
+            .field("headers", &self.headers)
+            .field("len", &len)
+            .field("path", &path)
+            .finish()
+    }
+}
+
+impl Read for MultipartReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(&mut (ref mut segment, _)) = self.segments.get_mut(self.current) {
+            let read = match *segment {
+                Segment::Bytes(ref mut cursor) => cursor.read(buf)?,
+                Segment::File(ref mut file) => file.read(buf)?
+            };
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            self.current += 1;
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for MultipartReader {
+    /// Moves to the given position, which cURL needs to send the body again.
+    ///
+    /// Only positions relative to the start are supported.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => pos,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "can only seek from the start"))
+        };
+        let mut offset = 0;
+        let mut current = None;
+        for (index, &mut (ref mut segment, len)) in self.segments.iter_mut().enumerate() {
+            let within = pos.saturating_sub(offset).min(len);
+            match *segment {
+                Segment::Bytes(ref mut cursor) => cursor.set_position(within),
+                Segment::File(ref mut file) => {
+                    file.seek(SeekFrom::Start(within))?;
+                }
+            }
+            if within < len && current.is_none() {
+                current = Some(index);
+            }
+            offset += len;
+        }
+        self.current = current.unwrap_or(self.segments.len());
+        Ok(pos.min(offset))
+    }
+}
+
+/// Escapes a name for use in a quoted header parameter, the way browsers do.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Generates a boundary that is very unlikely to appear in the body.
+fn generate_boundary() -> String {
+    format!("tokio-request-{:016x}{:016x}", random_u64(), random_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::post;
+    use test_server::{respond, serve, TempPath};
+    use super::{Multipart, Part};
+
+    #[test]
+    fn sends_all_parts() {
+        let url = serve(|request, stream| {
+            let mut body = request.header("Content-Type").unwrap_or("-").as_bytes().to_vec();
+            body.extend_from_slice(b"\n");
+            body.extend_from_slice(&request.body);
+            respond(stream, "200 OK", &[], &body);
+        }).into_string();
+        let path = TempPath::new("multipart.txt");
+        File::create(&path).unwrap().write_all(b"Hello from a file").unwrap();
+
+        let form = Multipart::new()
+            .text("full \"name\"", "Jane \"JD\" Doe")
+            .part("data", Part::bytes(vec![1, 2, 3])
+                .content_type("image/png".parse().unwrap())
+                .file_name("data.png")
+                .header("X-Part", "yes"))
+            .file("upload", &path);
+        let boundary = form.boundary().to_owned();
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url).multipart(form).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+
+        let expected = format!("multipart/form-data; boundary={b}\n\
+            --{b}\r\n\
+            Content-Disposition: form-data; name=\"full %22name%22\"\r\n\r\n\
+            Jane \"JD\" Doe\r\n\
+            --{b}\r\n\
+            Content-Disposition: form-data; name=\"data\"; filename=\"data.png\"\r\n\
+            Content-Type: image/png\r\n\
+            X-Part: yes\r\n\r\n\
+            \x01\x02\x03\r\n\
+            --{b}\r\n\
+            Content-Disposition: form-data; name=\"upload\"; filename=\"{f}\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n\
+            Hello from a file\r\n\
+            --{b}--\r\n", b = boundary, f = path.file_name().unwrap().to_str().unwrap());
+        assert_eq!(response.body_str(), Some(&*expected));
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = TempPath::new("multipart-missing");
+        let form = Multipart::new().file("upload", &path);
+
+        let mut evloop = Core::new().unwrap();
+        let future = post("http://127.0.0.1:1/").multipart(form).send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Io(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_part_headers() {
+        let form = Multipart::new().part("data", Part::text("a").header("X-Part", "a\r\nX-Injected: yes"));
+
+        let mut evloop = Core::new().unwrap();
+        let future = post("http://127.0.0.1:1/").multipart(form).send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::InvalidHeader(name)) => assert_eq!(name, "X-Part"),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn resends_body_after_redirect() {
+        let url = serve(|request, stream| {
+            if request.path.starts_with("/form") {
+                respond(stream, "307 Temporary Redirect", &[("Location", "/moved")], &[]);
+            } else {
+                respond(stream, "200 OK", &[], &request.body);
+            }
+        });
+        let path = TempPath::new("multipart-redirect");
+        File::create(&path).unwrap().write_all(&vec![b'f'; 100_000]).unwrap();
+        let form = Multipart::new()
+            .text("name", "value")
+            .file("upload", &path);
+        let (mut reader, _) = form.clone().into_reader().unwrap();
+        let mut expected = Vec::new();
+        reader.read_to_end(&mut expected).unwrap();
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(url.join("/form").unwrap().as_str()).multipart(form).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.body(), &expected[..]);
+    }
+
+    #[test]
+    fn boundaries_differ() {
+        assert_ne!(Multipart::new().boundary(), Multipart::new().boundary());
+    }
+}
@@ -0,0 +1,158 @@
+//! The module that contains the pool of reusable cURL handles.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use curl::easy::{Easy, ReadError, SeekResult};
+
+/// The default amount of idle handles a pool keeps around.
+pub const DEFAULT_POOL_SIZE: usize = 16;
+
+/// The default time after which idle handles are evicted from a pool.
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// A pool of cURL handles that are reused between requests.
+///
+/// Reusing a handle lets the requests share its connections, DNS cache and
+/// TLS session cache. Requests that use a pool check out a handle when they
+/// are sent, and the handle is reset and returned to the pool once the
+/// `Response` is dropped.
+///
+/// Cloning a pool yields a reference to the same pool.
+#[derive(Clone)]
+pub struct HandlePool {
+    inner: Arc<Mutex<Inner>>
+}
+
+struct Inner {
+    idle: Vec<(Easy, Instant)>,
+    idle_timeout: Duration,
+    max_idle: usize
+}
+
+impl HandlePool {
+    /// Creates a new pool that keeps up to `max_idle` handles around for up
+    /// to `idle_timeout` each.
+    pub fn new(max_idle: usize, idle_timeout: Duration) -> Self {
+        HandlePool {
+            inner: Arc::new(Mutex::new(Inner {
+                idle: Vec::new(),
+                idle_timeout,
+                max_idle
+            }))
+        }
+    }
+
+    /// Takes the most recently used idle handle out of the pool, or creates
+    /// a new one if there is none.
+    pub fn checkout(&self) -> Easy {
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict_idle();
+        inner.idle.pop()
+            .map(|(easy, _)| easy)
+            .unwrap_or_else(Easy::new)
+    }
+
+    /// Resets the given handle and puts it back into the pool.
+    ///
+    /// The handle is dropped if the pool is full already.
+    pub fn checkin(&self, mut easy: Easy) {
+        reset(&mut easy);
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict_idle();
+        if inner.idle.len() < inner.max_idle {
+            inner.idle.push((easy, Instant::now()));
+        }
+    }
+
+    /// Drops all handles that have been idle for longer than the idle timeout.
+    ///
+    /// This happens automatically whenever a handle is checked out or in.
+    pub fn evict_idle(&self) {
+        self.inner.lock().unwrap().evict_idle();
+    }
+
+    /// Gets the amount of idle handles in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.lock().unwrap().idle.len()
+    }
+}
+
+impl Default for HandlePool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOL_SIZE, DEFAULT_POOL_IDLE_TIMEOUT)
+    }
+}
+
+impl Debug for HandlePool {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let inner = self.inner.lock().unwrap();
+        fmt.debug_struct(stringify!(HandlePool))
+            .field("idle", &inner.idle.len())
+            .field("idle_timeout", &inner.idle_timeout)
+            .field("max_idle", &inner.max_idle)
+            .finish()
+    }
+}
+
+impl Inner {
+    fn evict_idle(&mut self) {
+        let idle_timeout = self.idle_timeout;
+        self.idle.retain(|&(_, since)| since.elapsed() < idle_timeout);
+    }
+}
+
+/// Resets all options of the handle while keeping its caches.
+///
+/// `Easy::reset` keeps the callbacks and cookies of the last request around,
+/// so the cookies are dropped and the callbacks are replaced with inert ones
+/// to release whatever they hold on to.
+fn reset(easy: &mut Easy) {
+    let _ = easy.cookie_list("ALL");
+    easy.reset();
+    let _ = easy.header_function(|_| true);
+    let _ = easy.write_function(|data| Ok(data.len()));
+    let _ = easy.read_function(|_| Err(ReadError::Abort));
+    let _ = easy.seek_function(|_| SeekResult::CantSeek);
+    let _ = easy.progress_function(|_, _, _, _| true);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::HandlePool;
+
+    #[test]
+    fn reuses_handles() {
+        let pool = HandlePool::new(1, Duration::from_secs(60));
+        let easy = pool.checkout();
+        let raw = easy.raw();
+        pool.checkin(easy);
+        assert_eq!(pool.idle(), 1);
+
+        let easy = pool.checkout();
+        assert_eq!(easy.raw(), raw);
+        assert_eq!(pool.idle(), 0);
+
+        // The pool is full with the second one
+        let other = pool.checkout();
+        pool.checkin(easy);
+        pool.checkin(other);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn evicts_idle_handles() {
+        let pool = HandlePool::new(4, Duration::from_millis(50));
+        pool.checkin(pool.checkout());
+        assert_eq!(pool.idle(), 1);
+
+        thread::sleep(Duration::from_millis(100));
+        pool.evict_idle();
+        assert_eq!(pool.idle(), 0);
+    }
+}
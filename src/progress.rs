@@ -0,0 +1,196 @@
+//! The module that contains the transfer progress reporting.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use abort::AbortHandle;
+use curl::easy::Easy;
+use error::Error;
+use timeout::Watchdog;
+
+/// A callback progress is reported to.
+type Callback = Arc<Mutex<Box<dyn FnMut(&Progress) -> bool + Send>>>;
+
+/// The progress of a transfer.
+///
+/// See [`Request::progress`](struct.Request.html#method.progress).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Progress {
+    download_total: Option<u64>,
+    downloaded: u64,
+    upload_total: Option<u64>,
+    uploaded: u64
+}
+
+/// The callback a request reports its progress to.
+#[derive(Clone)]
+pub(crate) struct ProgressHook {
+    callback: Callback,
+    interval: Option<Duration>
+}
+
+impl Progress {
+    /// Gets the number of body bytes the server is expected to send, if it
+    /// is known yet.
+    pub fn download_total(&self) -> Option<u64> {
+        self.download_total
+    }
+
+    /// Gets the number of body bytes received so far.
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded
+    }
+
+    /// Gets the number of body bytes that are going to be sent, if it is
+    /// known.
+    pub fn upload_total(&self) -> Option<u64> {
+        self.upload_total
+    }
+
+    /// Gets the number of body bytes sent so far.
+    pub fn uploaded(&self) -> u64 {
+        self.uploaded
+    }
+
+    /// Converts the numbers cURL reports, which are 0 while a total is
+    /// unknown.
+    fn from_curl(dltotal: f64, dlnow: f64, ultotal: f64, ulnow: f64) -> Self {
+        let total = |total: f64| if total > 0.0 { Some(total as u64) } else { None };
+        Progress {
+            download_total: total(dltotal),
+            downloaded: dlnow as u64,
+            upload_total: total(ultotal),
+            uploaded: ulnow as u64
+        }
+    }
+}
+
+impl ProgressHook {
+    /// Creates a hook calling the given callback at most once per interval,
+    /// if one is given.
+    pub fn new<F>(callback: F, interval: Option<Duration>) -> Self
+            where F: FnMut(&Progress) -> bool + Send + 'static {
+        ProgressHook {
+            callback: Arc::new(Mutex::new(Box::new(callback))),
+            interval
+        }
+    }
+}
+
+/// Configures the given handle to report its progress to the given hook and
+/// to stop as soon as the given abort handle is aborted or the given watchdog
+/// finds the transfer idle for too long.
+pub(crate) fn configure(easy: &mut Easy,
+                        hook: Option<&ProgressHook>,
+                        abort: Option<&AbortHandle>,
+                        mut watchdog: Watchdog) -> Result<(), Error> {
+    let hook = hook.cloned();
+    let abort = abort.cloned();
+    let mut last_call: Option<Instant> = None;
+    let mut last_progress = None;
+
+    easy.progress(true)?;
+    easy.progress_function(move |dltotal, dlnow, ultotal, ulnow| {
+        if abort.as_ref().is_some_and(AbortHandle::is_aborted) {
+            return false;
+        }
+        if !watchdog.check(dlnow, ulnow) {
+            return false;
+        }
+        let hook = match hook {
+            Some(ref hook) => hook,
+            None => return true
+        };
+
+        // cURL also calls this regularly while nothing happens.
+        let progress = Progress::from_curl(dltotal, dlnow, ultotal, ulnow);
+        if last_progress == Some(progress) {
+            return true;
+        }
+        if let Some(interval) = hook.interval {
+            let now = Instant::now();
+            if last_call.is_some_and(|last_call| now.duration_since(last_call) < interval) {
+                return true;
+            }
+            last_call = Some(now);
+        }
+        last_progress = Some(progress);
+        (hook.callback.lock().unwrap())(&progress)
+    })?;
+    Ok(())
+}
+
+impl Debug for ProgressHook {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(ProgressHook))
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::{get, post};
+    use test_server::{respond, serve};
+
+    #[test]
+    fn reports_download_and_upload_progress() {
+        let url = serve(|request, stream| respond(stream, "200 OK", &[], &vec![b'x'; request.body.len() * 2]));
+        let mut evloop = Core::new().unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let future = post(url.as_str())
+            .body(vec![b'y'; 50_000])
+            .progress(move |progress| {
+                sink.lock().unwrap().push(*progress);
+                true
+            })
+            .send(evloop.handle());
+        evloop.run(future).unwrap();
+
+        let last = *reports.lock().unwrap().last().unwrap();
+        assert_eq!(last.downloaded(), 100_000);
+        assert_eq!(last.download_total(), Some(100_000));
+        assert_eq!(last.uploaded(), 50_000);
+        assert_eq!(last.upload_total(), Some(50_000));
+    }
+
+    #[test]
+    fn throttles_reports() {
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], &vec![b'x'; 1_000_000]));
+        let mut evloop = Core::new().unwrap();
+
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        let future = get(url.as_str())
+            .progress_throttled(Duration::from_secs(60), move |_| {
+                *counter.lock().unwrap() += 1;
+                true
+            })
+            .send(evloop.handle());
+        evloop.run(future).unwrap();
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn aborts_transfer() {
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], &vec![b'x'; 1_000_000]));
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.as_str())
+            .progress(|progress| progress.downloaded() == 0)
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Curl(err)) => assert!(err.is_aborted_by_callback()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
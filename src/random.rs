@@ -0,0 +1,14 @@
+//! The module that contains the source of randomness.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Gets a random number.
+///
+/// Every `RandomState` is seeded differently, which is random enough for
+/// jitter and multipart boundaries, but not for anything security related.
+pub(crate) fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    hasher.finish()
+}
@@ -1,19 +1,38 @@
 //! The module that contains the request code.
 
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
-use std::io::Error;
+use std::io::{self, Read};
+use std::path::Path;
 use std::str;
 use std::sync::mpsc::channel;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use Method;
+use {BoxFuture, Method};
 
+use abort::AbortHandle;
+use auth::{self, Credentials};
+use body::Body;
+use bytes::Bytes;
+use cookie::CookieJar;
 use curl::easy::{Easy, List};
-use futures::{BoxFuture, failed, Future};
+use download::{Download, DownloadFuture, Target};
+use error::{Error, HttpError};
+use futures::{failed, Async, Future, Poll, Stream};
+use header::HeaderMap;
+use multipart::Multipart;
+use pool::HandlePool;
+use progress::{self, Progress, ProgressHook};
+use proxy::{Proxy, ProxyChoice};
 use response::Response;
-use tokio_core::reactor::Handle;
+use retry::{Retry, RetryPolicy};
+use stream::SendStreaming;
+use timeout::{Lookup, Timeouts};
+use tls::TlsConfig;
+use tokio_core::reactor::{Handle, Remote};
 use tokio_curl::Session;
-use url::Url;
+use transfer::Prepared;
+use url::{Host, Url};
+use url::form_urlencoded::Serializer;
 
 #[cfg(feature = "rustc-serialization")]
 use rustc_serialize;
@@ -42,21 +61,42 @@ pub const LOW_SPEED_TIME: u32 = 10;
 /// for more information.
 pub const MAX_REDIRECTS: u32 = 10;
 
+/// The time in seconds a transfer with a read timeout but without a low
+/// speed limit may stay below 1 byte per second, which is one day.
+const IDLE_CHECK_TIME: u64 = 24 * 60 * 60;
+
 /// Represents an HTTP request.
 ///
 /// While this can be used directly (and _must_ be for special HTTP verbs, it is
 /// preferred to use the [`get`](fn.get.html), [`post`](fn.post.html), etc. functions
 /// since they are shorter.
 pub struct Request {
-    body: Option<Vec<u8>>,
+    abort: Option<AbortHandle>,
+    auth: Option<Credentials>,
+    body: Option<Body>,
+    connect_timeout: Option<Duration>,
+    cookie_jar: Option<CookieJar>,
+    deadline: Option<Instant>,
+    default_headers: HeaderMap,
+    default_params: Vec<(String, String)>,
+    dns_timeout: Option<Duration>,
     follow_redirects: bool,
     handle: Option<Easy>,
-    headers: Vec<(String, String)>,
+    headers: HeaderMap,
+    invalid_form: Option<String>,
+    invalid_header: Option<String>,
     lowspeed_limits: Option<(u32, Duration)>,
     max_redirects: u32,
     method: Method,
     params: Vec<(String, String)>,
+    pool: Option<HandlePool>,
+    progress: Option<ProgressHook>,
+    proxy: ProxyChoice,
+    read_timeout: Option<Duration>,
+    record_redirects: bool,
+    retry: Option<RetryPolicy>,
     timeout: Option<Duration>,
+    tls: Option<TlsConfig>,
     url: Url
 }
 
@@ -64,25 +104,202 @@ impl Request {
     /// Creates a new instance of `Request`.
     pub fn new(url: &Url, method: Method) -> Self {
         Request {
+            abort: None,
+            auth: None,
             body: None,
+            connect_timeout: None,
+            cookie_jar: None,
+            deadline: None,
+            default_headers: HeaderMap::new(),
+            default_params: Vec::new(),
+            dns_timeout: None,
             follow_redirects: true,
             handle: None,
-            headers: Vec::new(),
+            headers: HeaderMap::new(),
+            invalid_form: None,
+            invalid_header: None,
             lowspeed_limits: Some((LOW_SPEED_LIMIT, Duration::from_secs(LOW_SPEED_TIME as u64))),
             max_redirects: MAX_REDIRECTS,
-            method: method,
+            method,
             params: Vec::new(),
+            pool: None,
+            progress: None,
+            proxy: ProxyChoice::Default,
+            read_timeout: None,
+            record_redirects: false,
+            retry: None,
             timeout: None,
+            tls: None,
             url: url.clone()
         }
     }
 
+    /// Attaches the given handle, which cancels the request when it is
+    /// aborted.
+    ///
+    /// See [`AbortHandle`](struct.AbortHandle.html) for more information.
+    pub fn abort_handle(mut self, handle: &AbortHandle) -> Self {
+        self.abort = Some(handle.clone());
+        self
+    }
+
+    /// Authenticates the request with the given user name and password
+    /// using HTTP Basic authentication.
+    ///
+    /// The credentials are sent with every request, so only use this over
+    /// HTTPS.
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.auth = Some(Credentials::Basic(user.to_owned(), password.to_owned()));
+        self
+    }
+
+    /// Authenticates the request with the given bearer token, e.g. an OAuth
+    /// 2.0 access token.
+    ///
+    /// An `Authorization` header set with [`Request::header`](#method.header)
+    /// takes precedence. Sending the request fails with
+    /// [`Error::InvalidHeader`](enum.Error.html#variant.InvalidHeader) if the
+    /// token contains line breaks.
+    pub fn bearer_auth(mut self, token: &str) -> Self {
+        self.auth = Some(Credentials::Bearer(token.to_owned()));
+        self
+    }
+
     /// Sets the body of the request as raw byte array.
     pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
-        self.body = Some(body.into());
+        self.body = Some(Body::Bytes(body.into()));
+        self
+    }
+
+    /// Streams the body of the request from the file at the given path.
+    ///
+    /// The file is opened when the request is sent and read piece by piece
+    /// as cURL sends it, so it is never loaded into memory as a whole.
+    pub fn body_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.body = Some(Body::File(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Streams the body of the request from the given reader.
+    ///
+    /// If the length of the body is not known up front, pass `None` and the
+    /// body will be sent with chunked transfer encoding.
+    ///
+    /// ## Remarks
+    /// The reader is read from on the event loop, so it should not block
+    /// for longer periods of time. Use
+    /// [`Request::body_stream`](#method.body_stream) for bodies that are
+    /// produced asynchronously.
+    pub fn body_reader<R: Read + Send + 'static>(mut self, reader: R, len: Option<u64>) -> Self {
+        self.body = Some(Body::Reader(Box::new(reader), len));
+        self
+    }
+
+    /// Streams the body of the request from the given stream of chunks.
+    ///
+    /// If the length of the body is not known up front, pass `None` and the
+    /// body will be sent with chunked transfer encoding. Chunks are sent as
+    /// the stream produces them, and the transfer waits while it has
+    /// nothing to send.
+    pub fn body_stream<S>(mut self, stream: S, len: Option<u64>) -> Self
+            where S: Stream<Item = Bytes, Error = io::Error> + Send + 'static {
+        self.body = Some(Body::Stream(Box::new(stream), len));
+        self
+    }
+
+    /// Sets the maximum time establishing the connection may take, including
+    /// resolving the host name and the TLS handshake.
+    ///
+    /// Expiring makes the request fail with
+    /// [`Error::ConnectTimeout`](enum.Error.html#variant.ConnectTimeout).
+    pub fn connect_timeout(mut self, duration: Duration) -> Self {
+        self.connect_timeout = Some(duration);
+        self
+    }
+
+    /// Sets the point in time by which the request must have completed,
+    /// including all redirects and retries.
+    ///
+    /// No retry is attempted that would start after the deadline. Passing
+    /// it makes the request fail with
+    /// [`Error::Deadline`](enum.Error.html#variant.Deadline), unless the
+    /// [`timeout`](#method.timeout) is shorter and expires first.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
         self
     }
 
+    /// Authenticates the request with the given user name and password
+    /// using HTTP Digest authentication.
+    ///
+    /// cURL first sends the request without credentials and answers the
+    /// challenge of the server, so the password never goes over the wire.
+    pub fn digest_auth(mut self, user: &str, password: &str) -> Self {
+        self.auth = Some(Credentials::Digest(user.to_owned(), password.to_owned()));
+        self
+    }
+
+    /// Sets the maximum time resolving the host name may take.
+    ///
+    /// cURL has no such timeout of its own, so the host name is resolved
+    /// before the transfer starts. Expiring makes the request fail with
+    /// [`Error::DnsTimeout`](enum.Error.html#variant.DnsTimeout).
+    ///
+    /// This does not apply to requests sent through a proxy, nor to the
+    /// hosts redirects lead to, which cURL resolves by itself.
+    pub fn dns_timeout(mut self, duration: Duration) -> Self {
+        self.dns_timeout = Some(duration);
+        self
+    }
+
+    /// Creates a new `Session` on the specified event loop and downloads the
+    /// response body straight to the file at the given path.
+    ///
+    /// See [`Request::download_to_with_session`](#method.download_to_with_session)
+    /// for more information.
+    pub fn download_to<P: AsRef<Path>>(self, path: P, h: Handle) -> BoxFuture<Download, Error> {
+        self.download_to_with_session(path, &Session::new(h))
+    }
+
+    /// Uses the given `Session` to send the request and writes the response
+    /// body to the file at the given path, without buffering it in memory.
+    ///
+    /// The body is written to `<path>.part` and moved to `path` once it is
+    /// complete. If an earlier download to the same path was interrupted, it
+    /// is resumed with a `Range` request. The `If-Range` header makes the
+    /// server send the whole body again if it has changed in the meantime.
+    /// A `416 Range Not Satisfiable` answer whose `Content-Range` matches the
+    /// length of the interrupted download means it was complete already.
+    ///
+    /// The future fails with [`Error::Http`](enum.Error.html#variant.Http)
+    /// if the server answers with a non-2xx status code, and keeps the
+    /// partial download in that case. The request is not retried.
+    ///
+    /// ## Panics
+    /// Panics in case of native exceptions in cURL.
+    pub fn download_to_with_session<P: AsRef<Path>>(mut self, path: P, session: &Session) -> BoxFuture<Download, Error> {
+        let target = Target::new(path.as_ref());
+        let partial = match target.partial() {
+            Some((len, validator)) => {
+                let resume = self.headers.insert("Range", &format!("bytes={}-", len))
+                    .and_then(|_| self.headers.insert("If-Range", &validator));
+                if resume.is_ok() { len } else { 0 }
+            },
+            None => 0
+        };
+
+        let method = self.method.clone();
+        let url = self.url.clone();
+        let record_redirects = self.record_redirects;
+        let res = self.prepare().and_then(|prepared| {
+            DownloadFuture::new(prepared, target, partial, method, url, record_redirects, session)
+        });
+        match res {
+            Ok(future) => Box::new(future),
+            Err(error) => Box::new(failed(error))
+        }
+    }
+
     /// Sets the option whether to follow 3xx-redirects or not.
     ///
     /// Defaults to `true`.
@@ -91,20 +308,78 @@ impl Request {
         self
     }
 
+    /// Url-encodes the given name-value pairs and uses them as the request
+    /// body. Also automatically sets the `Content-Type` to
+    /// `application/x-www-form-urlencoded`.
+    pub fn form<K: AsRef<str>, V: AsRef<str>>(self, pairs: &[(K, V)]) -> Self {
+        let mut serializer = Serializer::new(String::new());
+        for (name, value) in pairs {
+            serializer.append_pair(name.as_ref(), value.as_ref());
+        }
+        self.set_form(serializer.finish())
+    }
+
+    /// Serializes the given object to url-encoded form data and uses that as
+    /// the request body. Also automatically sets the `Content-Type` to
+    /// `application/x-www-form-urlencoded`.
+    ///
+    /// The object must serialize to a map, e.g. a struct. Sequences are sent
+    /// as repeated fields and `None` values are left out. The fields are
+    /// sent ordered by name.
+    ///
+    /// If serialization is not successful or the object contains nested
+    /// values, sending the request fails with
+    /// [`Error::InvalidForm`](enum.Error.html#variant.InvalidForm).
+    #[cfg(feature = "serde-serialization")]
+    pub fn form_serialize<T: serde::Serialize>(mut self, body: &T) -> Self {
+        let pairs = serde_json::to_string(body)
+            .and_then(|json| serde_json::from_str(&json))
+            .map_err(|err| err.to_string())
+            .and_then(form_pairs);
+        match pairs {
+            Ok(pairs) => {
+                let mut serializer = Serializer::new(String::new());
+                for (name, value) in pairs {
+                    serializer.append_pair(&name, &value);
+                }
+                self.set_form(serializer.finish())
+            },
+            Err(reason) => {
+                if self.invalid_form.is_none() {
+                    self.invalid_form = Some(reason);
+                }
+                self
+            }
+        }
+    }
+
     /// Adds an HTTP header to the request.
+    ///
+    /// If the name or value is invalid, sending the request fails with
+    /// [`Error::InvalidHeader`](enum.Error.html#variant.InvalidHeader).
     pub fn header(mut self, name: &str, value: &str) -> Self {
-        self.headers.push((name.to_owned(), value.to_owned()));
+        if self.headers.append(name, value).is_err() && self.invalid_header.is_none() {
+            self.invalid_header = Some(name.to_owned());
+        }
         self
     }
 
     /// Sets the given request headers.
     ///
     /// This overwrites all previously set headers.
-    pub fn headers(mut self, headers: Vec<(String, String)>) -> Self {
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
         self.headers = headers;
         self
     }
 
+    /// Does not send the header with the given name, even if it was added
+    /// before or is a default of the `Client` the request was created from.
+    pub fn without_header(mut self, name: &str) -> Self {
+        self.default_headers.remove(name);
+        self.headers.remove(name);
+        self
+    }
+
     /// Serializes the given object to JSON and uses that as the request body.
     /// Also automatically sets the `Content-Type` to `application/json`.
     ///
@@ -154,6 +429,27 @@ impl Request {
         self
     }
 
+    /// Uses the given multipart form as the request body. Also automatically
+    /// sets the `Content-Type` to `multipart/form-data` with the boundary of
+    /// the form.
+    ///
+    /// Files in the form are opened when the request is sent.
+    pub fn multipart(mut self, form: Multipart) -> Self {
+        let content_type = form.content_type();
+        self.body = Some(Body::Multipart(form));
+        self.set_content_type(&content_type)
+    }
+
+    /// Authenticates the request with the given user name and password
+    /// using NTLM.
+    ///
+    /// cURL performs the handshake with the server itself. The user name may
+    /// include the domain, like `DOMAIN\user`.
+    pub fn ntlm_auth(mut self, user: &str, password: &str) -> Self {
+        self.auth = Some(Credentials::Ntlm(user.to_owned(), password.to_owned()));
+        self
+    }
+
     /// Adds a URL parameter to the request.
     pub fn param(mut self, name: &str, value: &str) -> Self {
         self.params.push((name.to_owned(), value.to_owned()));
@@ -168,128 +464,189 @@ impl Request {
         self
     }
 
+    /// Reports the progress of the transfer to the given callback.
+    ///
+    /// The callback is called whenever cURL has transferred more data.
+    /// Returning `false` from it aborts the transfer, which then fails with
+    /// an [`Error::Curl`](enum.Error.html#variant.Curl) whose
+    /// `is_aborted_by_callback` is `true`.
+    ///
+    /// ```rust,ignore
+    /// let future = get(&url)
+    ///     .progress(|progress| {
+    ///         println!("{} of {:?} bytes", progress.downloaded(), progress.download_total());
+    ///         true
+    ///     })
+    ///     .send(evloop.handle());
+    /// ```
+    pub fn progress<F>(mut self, callback: F) -> Self
+            where F: FnMut(&Progress) -> bool + Send + 'static {
+        self.progress = Some(ProgressHook::new(callback, None));
+        self
+    }
+
+    /// Reports the progress of the transfer to the given callback at most
+    /// once per interval, e.g. to update a progress bar.
+    ///
+    /// See [`Request::progress`](#method.progress) for more information.
+    /// The final progress may not be reported, use
+    /// [`Response::timings`](struct.Response.html#method.timings) for that.
+    pub fn progress_throttled<F>(mut self, interval: Duration, callback: F) -> Self
+            where F: FnMut(&Progress) -> bool + Send + 'static {
+        self.progress = Some(ProgressHook::new(callback, Some(interval)));
+        self
+    }
+
+    /// Sends the request through the given proxy.
+    ///
+    /// Without a proxy, cURL picks one from the environment variables it
+    /// knows, like `http_proxy`.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = ProxyChoice::Proxy(proxy);
+        self
+    }
+
+    /// Sets the maximum time no bytes may be received or sent once the
+    /// connection has been established.
+    ///
+    /// Unlike the [`timeout`](#method.timeout), this does not limit slow but
+    /// steady transfers. Expiring makes the request fail with
+    /// [`Error::ReadTimeout`](enum.Error.html#variant.ReadTimeout).
+    pub fn read_timeout(mut self, duration: Duration) -> Self {
+        self.read_timeout = Some(duration);
+        self
+    }
+
+    /// Sets the option whether to keep the intermediate responses of followed
+    /// redirects, which are then available through
+    /// [`Response::redirects`](struct.Response.html#method.redirects).
+    ///
+    /// Defaults to `false`.
+    pub fn record_redirects(mut self, record: bool) -> Self {
+        self.record_redirects = record;
+        self
+    }
+
+    /// Sets the policy failed attempts to send the request are retried with.
+    ///
+    /// Retries are performed by [`Request::send`](#method.send) and
+    /// [`Client::send`](struct.Client.html#method.send).
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
     /// Creates a new `Session` on the specified event loop to send the HTTP request through
     /// and returns a future that fires off the request, parses the response and resolves to
     /// a `Response`-struct on success.
     ///
     /// ## Panics
     /// Panics in case of native exceptions in cURL.
-    pub fn send(self, h: Handle) -> BoxFuture<Response, Error> {
-        self.send_with_session(&Session::new(h))
+    pub fn send(self, h: Handle) -> ResponseFuture {
+        let session = Session::new(h.clone());
+        self.send_with_retries(&session, h.remote())
     }
 
     /// Uses the given `Session` to send the HTTP request through and returns a future that
     /// fires off the request, parses the response and resolves to a `Response`-struct on success.
     ///
+    /// The request is sent only once, since waiting between retries requires
+    /// access to the event loop. Use a [`Client`](struct.Client.html) to share
+    /// a session between requests that are retried.
+    ///
     /// ## Panics
     /// Panics in case of native exceptions in cURL.
-    pub fn send_with_session(mut self, session: &Session) -> BoxFuture<Response, Error> {
-        {
-            let mut query_pairs = self.url.query_pairs_mut();
-            for (key, value) in self.params {
-                query_pairs.append_pair(key.trim(), value.trim());
-            }
-        }
-        let headers = {
-            let mut list = List::new();
-            for (key, value) in self.headers {
-                list.append(&format!("{}: {}", key.trim(), value.trim())).expect("Failed to append header value to (native cURL) header list.");
-            }
-            list
-        };
+    pub fn send_with_session(self, session: &Session) -> ResponseFuture {
+        let method = self.method.clone();
+        let url = self.url.clone();
+        ResponseFuture::new(self.send_once(session), method, url)
+    }
 
-        let mut easy = self.handle.unwrap_or_else(|| Easy::new());
+    /// Sends the request through the given session exactly once.
+    pub(crate) fn send_once(self, session: &Session) -> BoxFuture<Response, Error> {
         let (header_tx, header_rx) = channel();
         let (body_tx, body_rx) = channel();
 
-        let config_res = {
-            // Make the borrow checker happy
-            let body = self.body;
-            let follow_redirects = self.follow_redirects;
-            let lowspeed_limits = self.lowspeed_limits;
-            let max_redirects = self.max_redirects;
-            let method = self.method;
-            let timeout = self.timeout;
-            let url = self.url;
-            let mut first_header = true;
-
-            // We cannot use try! here, since we're dealing with futures, not with Results
-            Ok(())
-                .and_then(|_| easy.accept_encoding(""))
-                .and_then(|_| easy.custom_request(method.as_ref()))
-                .and_then(|_| if follow_redirects {
-                    easy.follow_location(true)
-                        .and_then(|_| easy.max_redirections(max_redirects))
-                } else {
-                    Ok(())
-                })
-                .and_then(|_| easy.header_function(move |header| {
-                    match str::from_utf8(header) {
-                        Ok(s) => {
-                            let s = s.trim(); // Headers are \n-separated
-                            if !first_header && s.len() > 0 { // First header is HTTP status line, don't want that
-                                let _ = header_tx.send(s.to_owned());
-                            }
-                            first_header = false;
-                            true
-                        },
-                        Err(_) => false
-                    }
-                }))
-                .and_then(|_| easy.http_headers(headers))
-                .and_then(|_| if let Some((bytes, per_time)) = lowspeed_limits {
-                    easy.low_speed_limit(bytes)
-                        .and_then(|_| easy.low_speed_time(per_time))
-                } else {
-                    Ok(())
-                })
-                .and_then(|_| if method == Method::Head {
-                    easy.nobody(true)
-                } else {
-                    Ok(())
-                })
-                .and_then(|_| if let Some(ref body) = body {
-                    easy.post_fields_copy(body)
-                } else {
-                    Ok(())
-                })
-                .and_then(|_| if let Some(timeout) = timeout {
-                    easy.timeout(timeout)
-                } else {
-                    Ok(())
-                })
-                .and_then(|_| easy.url(url.as_str()))
-                .and_then(|_| easy.write_function(move |data| {
-                    let _ = body_tx.send(Vec::from(data));
-                    Ok(data.len())
-                }))
-        };
+        let record_redirects = self.record_redirects;
+        let url = self.url.clone();
+
+        let config_res = self.prepare().and_then(|mut prepared| {
+            prepared.easy.header_function(move |header| {
+                match str::from_utf8(header) {
+                    Ok(s) => {
+                        // Status lines are kept to tell the responses apart, and
+                        // leading whitespace marks folded lines.
+                        let s = s.trim_end(); // Headers are \n-separated
+                        if !s.is_empty() {
+                            let _ = header_tx.send(s.to_owned());
+                        }
+                        true
+                    },
+                    Err(_) => false
+                }
+            })?;
+            prepared.easy.write_function(move |data| {
+                let _ = body_tx.send(Vec::from(data));
+                Ok(data.len())
+            })?;
+            Ok(prepared)
+        });
 
         match config_res {
-            Ok(_) => session.perform(easy)
-                            .map_err(|err| err.into_error())
-                            .map(move |ez| {
-                                // In an ideal world where receiver_try_iter is stable
-                                // we could shorten this code to two lines.
-                                let body = {
-                                    let mut b = Vec::new();
-                                    while let Ok(item) = body_rx.try_recv() {
-                                        b.extend(item);
-                                    }
-                                    b
-                                };
-                                let headers = {
-                                    let mut h = Vec::new();
-                                    while let Ok(hdr) = header_rx.try_recv() {
-                                        h.push(hdr);
-                                    }
-                                    h
-                                };
-
-                                Response::new(ez, headers, body)
-                            })
-                            .boxed(),
-            Err(error) => failed(error.into()).boxed()
+            Ok(prepared) => {
+                let transfer = prepared.perform(session);
+                let pool = transfer.pool().cloned();
+                Box::new(transfer.map(move |ez| {
+                    // In an ideal world where receiver_try_iter is stable
+                    // we could shorten this code to two lines.
+                    let body = {
+                        let mut b = Vec::new();
+                        while let Ok(item) = body_rx.try_recv() {
+                            b.extend(item);
+                        }
+                        b
+                    };
+                    let headers = {
+                        let mut h = Vec::new();
+                        while let Ok(hdr) = header_rx.try_recv() {
+                            h.push(hdr);
+                        }
+                        h
+                    };
+
+                    Response::new(ez, headers, body)
+                        .keep_redirects(record_redirects)
+                        .recycle_into(pool)
+                        .sent_to(url)
+                }))
+            },
+            Err(error) => Box::new(failed(error))
+        }
+    }
+
+    /// Creates a new `Session` on the specified event loop and sends the request through it
+    /// without buffering the response body.
+    ///
+    /// See [`Request::send_streaming_with_session`](#method.send_streaming_with_session)
+    /// for more information.
+    pub fn send_streaming(self, h: Handle) -> SendStreaming {
+        self.send_streaming_with_session(&Session::new(h))
+    }
+
+    /// Uses the given `Session` to send the HTTP request through and returns a future that
+    /// resolves to a `StreamingResponse` as soon as the response headers have arrived.
+    ///
+    /// The response body is then delivered chunk by chunk through the
+    /// [`BodyStream`](struct.BodyStream.html) contained in the response, which
+    /// pauses the transfer while too many chunks wait for the consumer.
+    ///
+    /// ## Panics
+    /// Panics in case of native exceptions in cURL.
+    pub fn send_streaming_with_session(self, session: &Session) -> SendStreaming {
+        let follow_redirects = self.follow_redirects;
+        match self.prepare() {
+            Ok(prepared) => SendStreaming::new(prepared, follow_redirects, session),
+            Err(error) => SendStreaming::failed(error)
         }
     }
 
@@ -301,6 +658,19 @@ impl Request {
         self
     }
 
+    /// Sets the TLS settings for HTTPS requests.
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Sends the cookies from the given jar along with the request and stores
+    /// the cookies the server sets in it.
+    pub fn use_cookie_jar(mut self, jar: &CookieJar) -> Self {
+        self.cookie_jar = Some(jar.clone());
+        self
+    }
+
     /// Uses the given cURL handle in the request process reusing its resources
     /// and improving performance.
     ///
@@ -312,27 +682,347 @@ impl Request {
         self
     }
 
+    /// Takes the cURL handle for the request from the given pool and returns
+    /// it there once the request is done.
+    ///
+    /// A handle passed to [`Request::use_handle`](#method.use_handle) takes
+    /// precedence over the pool, but it is still returned to the pool.
+    pub fn use_pool(mut self, pool: &HandlePool) -> Self {
+        self.pool = Some(pool.clone());
+        self
+    }
+
+    /// Neither sends nor stores any cookies, even if the request was created
+    /// from a `Client` with a cookie jar.
+    pub fn without_cookie_jar(mut self) -> Self {
+        self.cookie_jar = None;
+        self
+    }
+
+    /// Sends the request without a proxy, even if the environment sets one.
+    pub fn without_proxy(mut self) -> Self {
+        self.proxy = ProxyChoice::Direct;
+        self
+    }
+
+    /// Consumes the request and applies its configuration to a cURL handle.
+    ///
+    /// Everything except for the header and write callbacks is configured
+    /// here, those depend on how the response is going to be consumed.
+    pub(crate) fn prepare(mut self) -> Result<Prepared, Error> {
+        if let Some(name) = self.invalid_header {
+            return Err(Error::InvalidHeader(name));
+        }
+        if let Some(reason) = self.invalid_form {
+            return Err(Error::InvalidForm(reason));
+        }
+        let params = merge_defaults(self.default_params, self.params);
+        {
+            let mut query_pairs = self.url.query_pairs_mut();
+            for (key, value) in params {
+                query_pairs.append_pair(key.trim(), value.trim());
+            }
+        }
+        let mut easy = match (self.handle, self.pool.as_ref()) {
+            (Some(handle), _) => handle,
+            (None, Some(pool)) => pool.checkout(),
+            (None, None) => Easy::new()
+        };
+        let (chunked, upload) = match self.body {
+            Some(body) => body.configure(&mut easy)?,
+            None => (false, None)
+        };
+        let headers = {
+            let mut list = List::new();
+            // An explicit `Authorization` header takes precedence.
+            if !self.headers.contains("Authorization") && !self.default_headers.contains("Authorization") {
+                if let Some(header) = self.auth.as_ref().map(Credentials::header).transpose()?.flatten() {
+                    list.append(&header)?;
+                }
+            }
+            let headers = &self.headers;
+            let defaults = self.default_headers.iter().filter(|&(key, _)| !headers.contains(key));
+            for (key, value) in defaults.chain(headers.iter()) {
+                list.append(&format!("{}: {}", key, value))
+                    .map_err(|_| Error::InvalidHeader(key.to_owned()))?;
+            }
+            if chunked {
+                list.append("Transfer-Encoding: chunked")?;
+            }
+            list
+        };
+
+        let follow_redirects = self.follow_redirects;
+        let lowspeed_limits = self.lowspeed_limits;
+        let max_redirects = self.max_redirects;
+        let method = self.method;
+        let mut timeouts = Timeouts::new(self.timeout, self.deadline)?;
+        timeouts.connect = self.connect_timeout;
+        timeouts.read_idle = self.read_timeout;
+        let url = self.url;
+        let lookup = match (self.dns_timeout, url.host(), url.port_or_known_default()) {
+            (Some(timeout), Some(Host::Domain(host)), Some(port)) if !self.proxy.is_used_for(&url) => {
+                Some(Lookup::new(host, port, timeout))
+            },
+            _ => None
+        };
+
+        easy.accept_encoding("")?;
+        if let Some(timeout) = timeouts.connect {
+            easy.connect_timeout(timeout)?;
+        }
+        easy.custom_request(method.as_ref())?;
+        if follow_redirects {
+            easy.follow_location(true)?;
+            easy.max_redirections(max_redirects)?;
+        }
+        easy.http_headers(headers)?;
+        match (lowspeed_limits, timeouts.read_idle) {
+            (Some((bytes, per_time)), _) => {
+                easy.low_speed_limit(bytes)?;
+                easy.low_speed_time(per_time)?;
+            },
+            // cURL only wakes up the progress callback of an idle transfer
+            // while it checks the speed, so check it without really aborting.
+            (None, Some(_)) => {
+                easy.low_speed_limit(1)?;
+                easy.low_speed_time(Duration::from_secs(IDLE_CHECK_TIME))?;
+            },
+            (None, None) => {}
+        }
+        if method == Method::Head {
+            easy.nobody(true)?;
+        }
+        easy.url(url.as_str())?;
+        if let Some(ref credentials) = self.auth {
+            credentials.configure(&mut easy)?;
+        }
+        progress::configure(&mut easy, self.progress.as_ref(), self.abort.as_ref(), timeouts.watchdog())?;
+        self.proxy.configure(&mut easy)?;
+        if let Some(ref tls) = self.tls {
+            tls.configure(&mut easy)?;
+        }
+        let cookies = match self.cookie_jar {
+            Some(jar) => {
+                let sent = jar.attach(&mut easy)?;
+                Some((jar, sent))
+            },
+            None => None
+        };
+
+        Ok(Prepared {
+            abort: self.abort,
+            cookies,
+            easy,
+            lookup,
+            pool: self.pool,
+            timeouts,
+            upload
+        })
+    }
+
+    /// Sends the request through the given session and retries it according
+    /// to its retry policy, if it has one.
+    pub(crate) fn send_with_retries(mut self, session: &Session, remote: &Remote) -> ResponseFuture {
+        let method = self.method.clone();
+        let url = self.url.clone();
+        let inner: BoxFuture<Response, Error> = match self.retry.take() {
+            Some(policy) => Box::new(Retry::new(self, policy, session.clone(), remote.clone())),
+            None => self.send_once(session)
+        };
+        ResponseFuture::new(inner, method, url)
+    }
+
+    /// Gets the deadline of the request, if it has one.
+    pub(crate) fn deadline_at(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Gets the method of the request.
+    pub(crate) fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Gets the retry policy of the request, if it has one.
+    pub(crate) fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    /// Gets the URL of the request.
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Replaces the method of the request.
+    pub(crate) fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Replaces the URL of the request with one that already carries its
+    /// query parameters, such as the effective URL of an earlier response.
+    pub(crate) fn with_url(mut self, url: Url) -> Self {
+        self.default_params.clear();
+        self.params.clear();
+        self.url = url;
+        self
+    }
+
+    /// Clones the request for sending it again, unless its body cannot be
+    /// read twice.
+    ///
+    /// The clone does not reuse the handle passed to `use_handle`.
+    pub(crate) fn try_clone(&self) -> Option<Request> {
+        let body = match self.body {
+            Some(ref body) => Some(body.try_clone()?),
+            None => None
+        };
+        Some(Request {
+            abort: self.abort.clone(),
+            auth: self.auth.clone(),
+            body,
+            connect_timeout: self.connect_timeout,
+            cookie_jar: self.cookie_jar.clone(),
+            deadline: self.deadline,
+            default_headers: self.default_headers.clone(),
+            default_params: self.default_params.clone(),
+            dns_timeout: self.dns_timeout,
+            follow_redirects: self.follow_redirects,
+            handle: None,
+            headers: self.headers.clone(),
+            invalid_form: self.invalid_form.clone(),
+            invalid_header: self.invalid_header.clone(),
+            lowspeed_limits: self.lowspeed_limits,
+            max_redirects: self.max_redirects,
+            method: self.method.clone(),
+            params: self.params.clone(),
+            pool: self.pool.clone(),
+            progress: self.progress.clone(),
+            proxy: self.proxy.clone(),
+            read_timeout: self.read_timeout,
+            record_redirects: self.record_redirects,
+            retry: self.retry.clone(),
+            timeout: self.timeout,
+            tls: self.tls.clone(),
+            url: self.url.clone()
+        })
+    }
+
+    /// Sets the headers and URL parameters inherited from a `Client`, along
+    /// with the name of the first default header that was invalid.
+    ///
+    /// They are sent unless the request sets a header or parameter with
+    /// the same name itself.
+    pub(crate) fn defaults(mut self, headers: &HeaderMap, invalid_header: Option<&str>, params: &[(String, String)]) -> Self {
+        self.default_headers = headers.clone();
+        self.default_params = params.to_vec();
+        self.invalid_header = invalid_header.map(str::to_owned);
+        self
+    }
+
+    /// Sets the `Content-Type` of the body, replacing the one set before.
+    fn set_content_type(mut self, value: &str) -> Self {
+        if self.headers.insert("Content-Type", value).is_err() && self.invalid_header.is_none() {
+            self.invalid_header = Some("Content-Type".to_owned());
+        }
+        self
+    }
+
+    fn set_form(mut self, body: String) -> Self {
+        self.body = Some(Body::Bytes(body.into_bytes()));
+        self.set_content_type("application/x-www-form-urlencoded")
+    }
+
     #[cfg(any(feature = "rustc-serialization", feature = "serde-serialization"))]
     fn set_json(mut self, body: Vec<u8>) -> Self {
-        self.body = Some(body);
-        self.header("Content-Type", "application/json")
+        self.body = Some(Body::Bytes(body));
+        self.set_content_type("application/json")
+    }
+}
+
+/// Flattens a serialized object into the name-value pairs of a form.
+///
+/// Fails with the reason if the object is not a map or contains nested
+/// values.
+#[cfg(feature = "serde-serialization")]
+fn form_pairs(value: serde_json::Value) -> Result<Vec<(String, String)>, String> {
+    use serde_json::Value;
+
+    fn to_string(name: &str, value: Value) -> Result<Option<String>, String> {
+        match value {
+            Value::Null => Ok(None),
+            Value::Bool(b) => Ok(Some(b.to_string())),
+            Value::I64(n) => Ok(Some(n.to_string())),
+            Value::U64(n) => Ok(Some(n.to_string())),
+            Value::F64(n) => Ok(Some(n.to_string())),
+            Value::String(s) => Ok(Some(s)),
+            Value::Array(_) | Value::Object(_) => Err(format!("form field '{}' cannot be nested", name))
+        }
     }
+
+    let map = match value {
+        Value::Object(map) => map,
+        _ => return Err("form data must serialize to a map".to_owned())
+    };
+    let mut pairs = Vec::new();
+    for (name, value) in map {
+        match value {
+            Value::Array(values) => {
+                for value in values {
+                    if let Some(value) = to_string(&name, value)? {
+                        pairs.push((name.clone(), value));
+                    }
+                }
+            },
+            value => if let Some(value) = to_string(&name, value)? {
+                pairs.push((name, value));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Puts the defaults in front of the given values, leaving out those whose
+/// name is among the values already.
+fn merge_defaults(defaults: Vec<(String, String)>, values: Vec<(String, String)>) -> Vec<(String, String)> {
+    let mut merged: Vec<_> = defaults.into_iter()
+        .filter(|default| !values.iter().any(|value| default.0.trim() == value.0.trim()))
+        .collect();
+    merged.extend(values);
+    merged
 }
 
 impl Debug for Request {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
-        let len = if let Some(ref body) = self.body {
-            body.len() as isize
-        } else {
-            -1isize
+        let len = match self.body.as_ref().and_then(|body| body.len()) {
+            Some(len) => len as i64,
+            None => -1i64
         };
         fmt.debug_struct(stringify!(Request))
+            .field("abort", &self.abort)
+            .field("auth", &self.auth)
             .field("body_len", &len)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("cookie_jar", &self.cookie_jar.is_some())
+            .field("deadline", &self.deadline)
+            .field("default_headers", &auth::redact_headers(&self.default_headers))
+            .field("default_params", &self.default_params)
+            .field("dns_timeout", &self.dns_timeout)
             .field("follow_redirects", &self.follow_redirects)
-            .field("headers", &self.headers)
+            .field("headers", &auth::redact_headers(&self.headers))
+            .field("lowspeed_limits", &self.lowspeed_limits)
+            .field("max_redirects", &self.max_redirects)
             .field("method", &self.method)
             .field("params", &self.params)
+            .field("progress", &self.progress)
+            .field("proxy", &self.proxy)
+            .field("read_timeout", &self.read_timeout)
+            .field("record_redirects", &self.record_redirects)
+            .field("retry", &self.retry)
             .field("reuses_handle", &self.handle.is_some())
+            .field("timeout", &self.timeout)
+            .field("tls", &self.tls)
+            .field("uses_pool", &self.pool.is_some())
             .field("url", &self.url)
             .finish()
     }
@@ -342,4 +1032,159 @@ impl Display for Request {
     fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
         write!(fmt, "{} {}", self.method, self.url)
     }
-}
\ No newline at end of file
+}
+
+/// A future that resolves to the buffered response of a request.
+///
+/// See [`Request::send`](struct.Request.html#method.send).
+#[must_use = "futures do nothing unless polled"]
+pub struct ResponseFuture {
+    error_for_status: bool,
+    inner: BoxFuture<Response, Error>,
+    method: Method,
+    url: Url
+}
+
+impl ResponseFuture {
+    /// Wraps the future of a request with the given method and URL.
+    fn new(inner: BoxFuture<Response, Error>, method: Method, url: Url) -> Self {
+        ResponseFuture {
+            error_for_status: false,
+            inner,
+            method,
+            url
+        }
+    }
+
+    /// Makes the future fail with [`Error::Http`](enum.Error.html#variant.Http)
+    /// if the server answers with a non-2xx status code.
+    ///
+    /// ```rust,ignore
+    /// let response = evloop.run(get(&url).send(evloop.handle()).error_for_status())?;
+    /// ```
+    pub fn error_for_status(mut self) -> Self {
+        self.error_for_status = true;
+        self
+    }
+}
+
+impl Future for ResponseFuture {
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Response, Error> {
+        let response = match self.inner.poll()? {
+            Async::Ready(response) => response,
+            Async::NotReady => return Ok(Async::NotReady)
+        };
+        if self.error_for_status && !response.is_success() {
+            let err = HttpError::new(self.method.clone(), self.url.clone(), &response);
+            return Err(Error::Http(Box::new(err)));
+        }
+        Ok(Async::Ready(response))
+    }
+}
+
+impl Debug for ResponseFuture {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(ResponseFuture))
+            .field("error_for_status", &self.error_for_status)
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_core::reactor::Core;
+
+    use str::post;
+    use test_server::{respond, serve};
+
+    fn form_server() -> String {
+        serve(|request, stream| {
+            let mut body = request.header("Content-Type").unwrap_or("-").as_bytes().to_vec();
+            body.push(b' ');
+            body.extend_from_slice(&request.body);
+            respond(stream, "200 OK", &[], &body);
+        }).into_string()
+    }
+
+    #[test]
+    fn form_body() {
+        let url = form_server();
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .form(&[("name", "Jane Doe"), ("note", "a&b=c")])
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("application/x-www-form-urlencoded name=Jane+Doe&note=a%26b%3Dc"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-serialization")]
+    fn serialized_form_body() {
+        use std::collections::BTreeMap;
+
+        let mut form = BTreeMap::new();
+        form.insert("missing", vec![]);
+        form.insert("tags", vec!["a b", "c"]);
+        form.insert("user", vec!["jane"]);
+
+        let url = form_server();
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .form_serialize(&form)
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("application/x-www-form-urlencoded tags=a+b&tags=c&user=jane"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-serialization")]
+    fn rejects_nested_form_fields() {
+        use std::collections::BTreeMap;
+
+        use error::Error;
+
+        let mut form = BTreeMap::new();
+        form.insert("user", BTreeMap::<String, String>::new());
+
+        let url = form_server();
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .form_serialize(&form)
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::InvalidForm(reason)) => assert!(reason.contains("'user'")),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        let future = post(&url)
+            .form_serialize(&["not", "a", "map"])
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::InvalidForm(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn body_replaces_content_type() {
+        let url = serve(|request, stream| {
+            let types: Vec<_> = request.headers.iter()
+                .filter(|header| header.0.eq_ignore_ascii_case("Content-Type"))
+                .map(|header| header.1.as_str())
+                .collect();
+            respond(stream, "200 OK", &[], types.join(", ").as_bytes());
+        }).into_string();
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url)
+            .header("Content-Type", "text/plain")
+            .form(&[("name", "jane")])
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("application/x-www-form-urlencoded"));
+    }
+}
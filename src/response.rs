@@ -2,10 +2,20 @@
 
 use std::convert::From;
 use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
 use std::str;
+use std::time::{Duration, SystemTime};
 
+use curl;
 use curl::easy::Easy;
+use date::parse_http_date;
+use header::{self, HeaderMap};
 use mime::Mime;
+use pool::HandlePool;
+use status::StatusCode;
+use timings::Timings;
+use url::Url;
 
 #[cfg(feature = "rustc-serialization")]
 use rustc_serialize;
@@ -21,38 +31,115 @@ use std::io::{Error, ErrorKind};
 /// Represents an HTTP response.
 pub struct Response {
     body: Vec<u8>,
-    handle: Easy,
-    headers: Vec<(String, String)>,
+    handle: Option<Easy>,
+    headers: HeaderMap,
+    interim: Vec<InterimResponse>,
+    pool: Option<HandlePool>,
+    reason: Option<String>,
+    redirects: Vec<Redirect>,
+    status_code: u16,
+    url: Option<Url>,
+    version: Option<HttpVersion>
+}
+
+/// A version of the HTTP protocol.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum HttpVersion {
+    /// HTTP/1.0
+    Http10,
+    /// HTTP/1.1
+    Http11,
+    /// HTTP/2
+    Http2,
+    /// HTTP/3
+    Http3
+}
+
+/// The head of a response that came before the final one, e.g. an
+/// informational response, an authentication challenge or a redirect.
+///
+/// See [`Response::interim_responses`](struct.Response.html#method.interim_responses)
+/// for more information.
+#[derive(Clone, Debug)]
+pub struct InterimResponse {
+    headers: HeaderMap,
+    reason: Option<String>,
     status_code: u16
 }
 
+/// An intermediate response that redirected the request elsewhere.
+///
+/// See [`Request::record_redirects`](struct.Request.html#method.record_redirects)
+/// for more information.
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    headers: HeaderMap,
+    status_code: u16
+}
+
+/// The status line and headers of a single response.
+#[derive(Debug, Default)]
+pub(crate) struct Head {
+    pub headers: HeaderMap,
+    pub reason: Option<String>,
+    pub status_code: u16,
+    pub version: Option<HttpVersion>
+}
+
+/// A line of a response head, as cURL hands it to the header callback.
+pub(crate) enum HeadLine<'a> {
+    /// The status line that starts a response, along with its status code.
+    Status(&'a str, u16),
+    /// A header, or the continuation of a folded one.
+    Header(&'a str),
+    /// The blank line that ends a head.
+    End
+}
+
+/// The header lines of a transfer, along with the status code and the
+/// headers of the latest response.
+#[derive(Debug, Default)]
+pub(crate) struct HeadLines {
+    /// The header lines of the latest response.
+    pub head: Vec<String>,
+    /// All lines including the status lines, as `Response::new` takes them.
+    pub lines: Vec<String>,
+    /// The status code of the latest response.
+    pub status_code: u16
+}
+
 impl Response {
     /// Creates a `Response` from the results of a successful request.
     ///
+    /// The header lines may include the status lines of informational
+    /// responses and redirects that came before the final response.
+    ///
     /// You usually don't create a response this way, but get one as result
     /// from `Request.send(...)`.
-    pub fn new(mut easy: Easy, headers: Vec<String>, body: Vec<u8>) -> Response {
-        let headers =  {
-            let mut vec = Vec::new();
-            for header in headers {
-                let splitted: Vec<_> = header.splitn(2, ": ")
-                                             .map(|part| part.trim())
-                                             .filter(|part| part.len() > 0)
-                                             .collect();
-                if splitted.len() != 2 {
-                    continue;
-                }
-
-                vec.push((splitted[0].to_owned(), splitted[1].to_owned()));
-            }
-            vec
-        };
+    pub fn new(easy: Easy, headers: Vec<String>, body: Vec<u8>) -> Response {
+        let mut heads = parse_heads(headers);
+        let last = heads.pop().unwrap_or_default();
+        // Leaves out informational responses, authentication challenges and
+        // the responses of proxies to `CONNECT`.
+        let redirects = heads.iter()
+            .filter(|head| (300..=399).contains(&head.status_code) && head.headers.location().is_some())
+            .map(|head| Redirect { headers: head.headers.clone(), status_code: head.status_code })
+            .collect();
+        let interim = heads.into_iter()
+            .map(|head| InterimResponse { headers: head.headers, reason: head.reason, status_code: head.status_code })
+            .collect();
         let status_code = easy.response_code().expect("Failed to get the response status code from cURL.") as u16;
         Response {
-            body: body,
-            handle: easy,
-            headers: headers,
-            status_code: status_code
+            body,
+            handle: Some(easy),
+            headers: last.headers,
+            interim,
+            pool: None,
+            reason: last.reason,
+            redirects,
+            status_code,
+            url: None,
+            version: last.version
         }
     }
 
@@ -76,8 +163,7 @@ impl Response {
     /// This function also returns none if there has been an error parsing
     /// the mime type.
     pub fn content_type(&self) -> Option<Mime> {
-        self.header("Content-Type")
-            .and_then(|h| h.parse::<Mime>().ok())
+        self.headers.content_type()
     }
 
     /// Returns `Ok` in case of a successful status code and `Err` if not.
@@ -85,7 +171,11 @@ impl Response {
     /// This returns the `Response` in both cases and uses the `Ok`
     /// and `Err` variants to indicate the result. See
     /// [`Response::is_success`](struct.Response.html#method.is_success)
-    /// for more information.
+    /// for more information. Use
+    /// [`ResponseFuture::error_for_status`](struct.ResponseFuture.html#method.error_for_status)
+    /// to get an [`Error`](enum.Error.html) instead.
+    // Handing back the whole response in both cases is the point here.
+    #[allow(clippy::result_large_err)]
     pub fn ensure_success(self) -> Result<Response, Response> {
         if self.is_success() {
             Ok(self)
@@ -96,27 +186,94 @@ impl Response {
 
     /// Attempts to get a single header value.
     ///
-    /// If there are multiple headers with the same name, this method returns
-    /// the first one. If you need to get access to the other values, use
+    /// The name is matched case-insensitively. If there are multiple headers
+    /// with the same name, this method returns the first one. If you need to
+    /// get access to the other values, use
     /// [`Response::headers()`](struct.Response.html#method.headers).
-    pub fn header(&self, name: &str) -> Option<&String> {
-        self.headers.iter().filter(|kvp| kvp.0 == name)
-                           .nth(0)
-                           .map(|kvp| &kvp.1)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
     }
 
     /// Gets all response headers.
-    pub fn headers(&self) -> &Vec<(String, String)> {
+    ///
+    /// If redirects were followed, these are the headers of the final
+    /// response only. The headers of the responses before it are available
+    /// through [`Response::interim_responses()`](struct.Response.html#method.interim_responses).
+    pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 
+    /// Gets the HTTP version of the final response.
+    ///
+    /// Returns `None` if the response had no valid status line.
+    pub fn http_version(&self) -> Option<HttpVersion> {
+        self.version
+    }
+
+    /// Gets the URL the final response came from, after following redirects.
+    ///
+    /// Falls back to the URL the request was sent to if cURL does not know
+    /// it or it cannot be parsed.
+    pub fn effective_url(&self) -> Url {
+        self.easy().effective_url().ok()
+            .and_then(|url| url)
+            .and_then(|url| Url::parse(url).ok())
+            .or_else(|| self.url.clone())
+            .expect("Response without URL.")
+    }
+
+    /// Gets the heads of all responses that were received before the final
+    /// one, in the order they were received.
+    ///
+    /// These include informational responses like `100 Continue`,
+    /// authentication challenges cURL answered, redirects it followed and
+    /// the responses of proxies to `CONNECT`.
+    pub fn interim_responses(&self) -> &[InterimResponse] {
+        &self.interim
+    }
+
+    /// Gets the local address the request was sent from.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        let easy = self.easy();
+        socket_addr(easy.local_ip(), easy.local_port())
+    }
+
+    /// Gets the intermediate responses that redirected the request, in the
+    /// order they were received.
+    ///
+    /// This is empty unless the request was sent with
+    /// [`Request::record_redirects`](struct.Request.html#method.record_redirects).
+    pub fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    /// Gets the number of redirects that were followed.
+    pub fn redirect_count(&self) -> u32 {
+        self.easy().redirect_count().unwrap_or(0)
+    }
+
+    /// Gets the address of the server the final response came from.
+    ///
+    /// This is the address of the proxy if the request was sent through one.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        let easy = self.easy();
+        socket_addr(easy.primary_ip(), easy.primary_port())
+    }
+
     /// Checks whether the returned status code represents a success
     /// (HTTP status code 2xx) or not.
     pub fn is_success(&self) -> bool {
-        match self.status_code {
-            200...299 => true,
-            _ => false
-        }
+        is_success(self.status_code)
+    }
+
+    /// Gets how long the server asks the client to wait before sending the
+    /// next request, as told by the `Retry-After` header.
+    ///
+    /// The header may either contain the amount of seconds or an HTTP-date,
+    /// which is converted to the time left until then. Returns `None` if
+    /// there is no such header or it cannot be parsed.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.header("Retry-After").and_then(parse_retry_after)
     }
 
     /// Attempts to decode the response body from JSON to an
@@ -161,12 +318,283 @@ impl Response {
         self.json::<serde_json::Value>()
     }
 
+    /// Gets the reason phrase the server sent along with the status code,
+    /// e.g. `Not Found`.
+    ///
+    /// HTTP/2 and later do not send reason phrases. Use
+    /// [`StatusCode::canonical_reason`](struct.StatusCode.html#method.canonical_reason)
+    /// to get the registered one.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
     /// Gets the response status code.
+    pub fn status(&self) -> StatusCode {
+        StatusCode(self.status_code)
+    }
+
+    /// Gets the response status code as number.
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    /// Gets how long the phases of the transfer took and how much data
+    /// was moved.
+    pub fn timings(&self) -> Timings {
+        self.handle.as_ref().map(Timings::from_easy).unwrap_or_default()
+    }
+}
+
+impl Response {
+    /// Gets the handle the request was sent with.
+    fn easy(&self) -> &Easy {
+        self.handle.as_ref().expect("Response without handle.")
+    }
+
+    /// Drops the recorded redirects, unless they were asked for.
+    pub(crate) fn keep_redirects(mut self, keep: bool) -> Self {
+        if !keep {
+            self.redirects = Vec::new();
+        }
+        self
+    }
+
+    /// Makes the response return its handle to the given pool when dropped.
+    pub(crate) fn recycle_into(mut self, pool: Option<HandlePool>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Sets the URL the request was sent to, which the effective URL falls
+    /// back to.
+    pub(crate) fn sent_to(mut self, url: Url) -> Self {
+        self.url = Some(url);
+        self
+    }
+}
+
+impl InterimResponse {
+    /// Attempts to get a single header value.
+    ///
+    /// The name is matched case-insensitively. If there are multiple headers
+    /// with the same name, this method returns the first one.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Gets the headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Gets the reason phrase the server sent along with the status code.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Gets the status code of the response.
     pub fn status_code(&self) -> u16 {
         self.status_code
     }
 }
 
+impl Redirect {
+    /// Gets the headers of the redirecting response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Gets the URL the response redirected to, as given by the `Location`
+    /// header.
+    ///
+    /// Relative URLs are returned as they are.
+    pub fn location(&self) -> Option<&str> {
+        self.headers.location()
+    }
+
+    /// Gets the status code of the redirecting response.
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+}
+
+impl HeadLines {
+    /// Adds the given line.
+    pub fn push(&mut self, line: HeadLine) {
+        match line {
+            HeadLine::Status(line, status_code) => {
+                self.status_code = status_code;
+                self.head.clear();
+                self.lines.push(line.to_owned());
+            },
+            HeadLine::Header(line) => {
+                self.head.push(line.to_owned());
+                self.lines.push(line.to_owned());
+            },
+            HeadLine::End => {}
+        }
+    }
+
+    /// Parses the headers of the latest response.
+    pub fn headers(&self) -> HeaderMap {
+        parse_headers(self.head.clone())
+    }
+}
+
+impl HttpVersion {
+    /// Parses the version from the start of a status line like
+    /// `HTTP/1.1 200 OK`.
+    pub(crate) fn from_status_line(line: &str) -> Option<Self> {
+        match line.split_whitespace().next()? {
+            "HTTP/1.0" => Some(HttpVersion::Http10),
+            "HTTP/1.1" => Some(HttpVersion::Http11),
+            "HTTP/2" | "HTTP/2.0" => Some(HttpVersion::Http2),
+            "HTTP/3" => Some(HttpVersion::Http3),
+            _ => None
+        }
+    }
+}
+
+/// Configures the given handle to hand every line of the response heads to
+/// the given callback.
+pub(crate) fn read_heads<F>(easy: &mut Easy, mut callback: F) -> Result<(), curl::Error>
+        where F: FnMut(HeadLine) + Send + 'static {
+    easy.header_function(move |header| {
+        let line = match str::from_utf8(header) {
+            // Leading whitespace marks folded lines.
+            Ok(line) => line.trim_end(),
+            Err(_) => return false
+        };
+        callback(if line.starts_with("HTTP/") {
+            HeadLine::Status(line, parse_status_code(line))
+        } else if line.is_empty() {
+            HeadLine::End
+        } else {
+            HeadLine::Header(line)
+        });
+        true
+    })
+}
+
+/// Splits the raw header lines of a transfer into the heads of the
+/// responses that were received, one per status line.
+///
+/// Lines before the first status line make up a head of their own.
+pub(crate) fn parse_heads(lines: Vec<String>) -> Vec<Head> {
+    let mut heads = Vec::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    let mut leading = Vec::new();
+    for line in lines {
+        if line.starts_with("HTTP/") {
+            if let Some((status_line, lines)) = current.take() {
+                heads.push(parse_head(&status_line, lines));
+            }
+            current = Some((line, Vec::new()));
+        } else if let Some((_, ref mut lines)) = current {
+            lines.push(line);
+        } else {
+            leading.push(line);
+        }
+    }
+    if !leading.is_empty() {
+        heads.insert(0, Head { headers: parse_headers(leading), reason: None, status_code: 0, version: None });
+    }
+    if let Some((status_line, lines)) = current {
+        heads.push(parse_head(&status_line, lines));
+    }
+    heads
+}
+
+fn parse_head(status_line: &str, lines: Vec<String>) -> Head {
+    Head {
+        headers: parse_headers(lines),
+        reason: parse_reason(status_line),
+        status_code: parse_status_code(status_line),
+        version: HttpVersion::from_status_line(status_line)
+    }
+}
+
+/// Gets the status code from a status line like `HTTP/1.1 404 Not Found`.
+fn parse_status_code(status_line: &str) -> u16 {
+    status_line.split_whitespace()
+               .nth(1)
+               .and_then(|code| code.parse().ok())
+               .unwrap_or(0)
+}
+
+/// Gets the reason phrase from a status line like `HTTP/1.1 404 Not Found`.
+pub(crate) fn parse_reason(status_line: &str) -> Option<String> {
+    status_line.splitn(3, ' ')
+               .nth(2)
+               .map(str::trim)
+               .filter(|reason| !reason.is_empty())
+               .map(str::to_owned)
+}
+
+/// Splits raw header lines into name-value pairs, as described in
+/// RFC 7230, section 3.2.
+///
+/// The whitespace around values is optional and lines starting with
+/// whitespace continue the value of the previous header (obsolete line
+/// folding). Lines that do not contain a valid header are skipped, along
+/// with their continuation lines.
+pub(crate) fn parse_headers(lines: Vec<String>) -> HeaderMap {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut folding = false;
+    for line in lines {
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let (true, Some(last)) = (folding, headers.last_mut()) {
+                let continuation = trim_ows(line);
+                if !continuation.is_empty() {
+                    if !last.1.is_empty() {
+                        last.1.push(' ');
+                    }
+                    last.1.push_str(continuation);
+                }
+            }
+            continue;
+        }
+
+        let mut kvp = line.splitn(2, ':');
+        folding = match (kvp.next(), kvp.next()) {
+            // No whitespace is allowed between the name and the colon.
+            (Some(name), Some(value)) if header::is_token(name) => {
+                headers.push((name.to_owned(), trim_ows(value).to_owned()));
+                true
+            },
+            _ => false
+        };
+    }
+    HeaderMap::from_entries(headers)
+}
+
+/// Trims the optional whitespace around a header value.
+fn trim_ows(value: &str) -> &str {
+    value.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+/// Parses the value of a `Retry-After` header.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => parse_http_date(value).map(|date| {
+            date.duration_since(SystemTime::now()).unwrap_or(Duration::from_secs(0))
+        })
+    }
+}
+
+fn socket_addr<E>(ip: Result<Option<&str>, E>, port: Result<u16, E>) -> Option<SocketAddr> {
+    let ip: IpAddr = ip.ok()??.parse().ok()?;
+    Some(SocketAddr::new(ip, port.ok()?))
+}
+
+/// Checks whether the given status code is a 2xx one.
+pub(crate) fn is_success(status_code: u16) -> bool {
+    (200..=299).contains(&status_code)
+}
+
 impl AsRef<[u8]> for Response {
     fn as_ref(&self) -> &[u8] {
         &self.body
@@ -178,21 +606,165 @@ impl Debug for Response {
         fmt.debug_struct(stringify!(Response))
             .field("body_str", &self.body_str())
             .field("headers", &self.headers)
+            .field("interim", &self.interim)
+            .field("reason", &self.reason)
+            .field("redirects", &self.redirects)
             .field("status_code", &self.status_code)
+            .field("timings", &self.timings())
+            .field("url", &self.url)
+            .field("version", &self.version)
             .finish()
     }
 }
 
+impl Drop for Response {
+    fn drop(&mut self) {
+        if let (Some(pool), Some(handle)) = (self.pool.take(), self.handle.take()) {
+            pool.checkin(handle);
+        }
+    }
+}
+
 impl From<Response> for Easy {
     /// Consumes the response and returns the underlying cURL handle
     /// used for the request so that it can be reused.
-    fn from(response: Response) -> Self {
-        response.handle
+    ///
+    /// The handle is not returned to the pool it came from, if any.
+    fn from(mut response: Response) -> Self {
+        response.handle.take().expect("Response without handle.")
     }
 }
 
 impl From<Response> for Vec<u8> {
-    fn from(response: Response) -> Self {
-        response.body
+    fn from(mut response: Response) -> Self {
+        mem::take(&mut response.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use tokio_core::reactor::Core;
+
+    use str::get;
+    use test_server::{respond, serve};
+    use super::{parse_headers, HttpVersion};
+
+    #[test]
+    fn follows_redirect_chain() {
+        let url = serve(|request, stream| {
+            match request.path.split('?').next().unwrap() {
+                "/a" => respond(stream, "302 Found", &[("Location", "/b"), ("X-Hop", "a")], b""),
+                "/b" => respond(stream, "301 Moved Permanently", &[("Location", "/c"), ("X-Hop", "b")], b""),
+                _ => respond(stream, "200 OK", &[("X-Final", "yes")], b"done")
+            }
+        });
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.join("a").unwrap().as_str()).record_redirects(true).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("done"));
+        assert_eq!(response.effective_url(), url.join("c").unwrap());
+        assert_eq!(response.redirect_count(), 2);
+        assert_eq!(response.http_version(), Some(HttpVersion::Http11));
+        assert_eq!(response.header("X-Final"), Some("yes"));
+        assert_eq!(response.header("X-Hop"), None);
+
+        let redirects: Vec<_> = response.redirects()
+            .iter()
+            .map(|redirect| (redirect.status_code(), redirect.location()))
+            .collect();
+        assert_eq!(redirects, vec![(302, Some("/b")), (301, Some("/c"))]);
+
+        let remote = response.remote_addr().unwrap();
+        assert_eq!(remote.ip(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+        assert_eq!(Some(remote.port()), url.port());
+        assert!(response.local_addr().is_some());
+
+        let future = get(url.join("a").unwrap().as_str()).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.redirect_count(), 2);
+        assert!(response.redirects().is_empty());
+    }
+
+    #[test]
+    fn records_only_redirects() {
+        let url = serve(|request, stream| {
+            match (request.path.split('?').next().unwrap(), request.header("Authorization")) {
+                ("/a", _) => respond(stream, "302 Found", &[("Location", "/b")], b""),
+                (_, Some(_)) => respond(stream, "200 OK", &[], b"welcome"),
+                _ => respond(stream, "401 Unauthorized", &[
+                    ("WWW-Authenticate", "Digest realm=\"test\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", qop=\"auth\""),
+                    ("Location", "/elsewhere")
+                ], b"")
+            }
+        });
+        let mut evloop = Core::new().unwrap();
+
+        let future = get(url.join("a").unwrap().as_str())
+            .digest_auth("user", "pass")
+            .record_redirects(true)
+            .send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.body_str(), Some("welcome"));
+        let redirects: Vec<_> = response.redirects()
+            .iter()
+            .map(|redirect| (redirect.status_code(), redirect.location()))
+            .collect();
+        assert_eq!(redirects, vec![(302, Some("/b"))]);
+
+        let interim: Vec<_> = response.interim_responses()
+            .iter()
+            .map(|head| (head.status_code(), head.reason()))
+            .collect();
+        assert_eq!(interim, vec![(302, Some("Found")), (401, Some("Unauthorized"))]);
+        assert!(response.interim_responses()[1].header("WWW-Authenticate").is_some());
+    }
+
+    #[test]
+    fn parses_headers_per_rfc_7230() {
+        let lines = vec![
+            "Content-Type:text/plain",
+            "X-Folded: first",
+            " second",
+            "\tthird",
+            "X-Empty:",
+            "Bad Name: dropped",
+            " dropped as well",
+            "no colon",
+            "X-Padded: \t value \t"
+        ];
+        let headers = parse_headers(lines.into_iter().map(str::to_owned).collect());
+        let expected = vec![
+            ("Content-Type", "text/plain"),
+            ("X-Folded", "first second third"),
+            ("X-Empty", ""),
+            ("X-Padded", "value")
+        ];
+        assert_eq!(headers.iter().collect::<Vec<_>>(), expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn keeps_final_headers_after_continue() {
+        let url = serve(|_, stream| {
+            let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\nX-Interim: yes\r\n\r\n\
+                                       HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-Folded: a\r\n b\r\n\r\nok");
+        });
+        let mut evloop = Core::new().unwrap();
+
+        let response = evloop.run(get(url.as_str()).send(evloop.handle())).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header("X-Interim"), None);
+        assert_eq!(response.header("X-Folded"), Some("a b"));
+        let interim = &response.interim_responses()[0];
+        assert_eq!(response.interim_responses().len(), 1);
+        assert_eq!((interim.status_code(), interim.reason()), (100, Some("Continue")));
+        assert_eq!(interim.header("X-Interim"), Some("yes"));
+
+        let response = evloop.run(get(url.as_str()).send_streaming(evloop.handle())).unwrap();
+        assert_eq!(response.header("X-Interim"), None);
+        assert_eq!(response.header("X-Folded"), Some("a b"));
+    }
+}
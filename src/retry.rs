@@ -0,0 +1,408 @@
+//! The module that contains the retry policy and the future retrying requests.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use error::Error;
+use futures::{Async, Future, Poll};
+use random::random_u64;
+use request::Request;
+use response::Response;
+use tokio_core::reactor::{Remote, Timeout};
+use tokio_curl::Session;
+use {BoxFuture, Method};
+
+/// The default maximum amount of attempts, including the first one.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// The default delay before the first retry.
+pub const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// The default upper bound for the delay between two attempts.
+pub const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// The default upper bound for the wait a `Retry-After` header may ask for.
+pub const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Decides whether the outcome of an attempt should be retried.
+type Predicate = Arc<dyn Fn(&Method, Result<&Response, &Error>) -> bool + Send + Sync>;
+
+/// Describes when and how often a failed request is retried.
+///
+/// The delay before the `n`-th retry is chosen at random between zero and
+/// `base_delay * 2^(n - 1)`, capped at `max_delay` ("full jitter"). This keeps
+/// many clients from retrying in lockstep.
+///
+/// By default, only requests with idempotent methods are retried, and only if
+/// they failed with a 502, 503 or 504 response, a connection error or a
+/// timeout. See [`RetryPolicy::is_transient`](#method.is_transient).
+///
+/// With [`RetryPolicy::honor_retry_after`](#method.honor_retry_after),
+/// 429 and 503 responses that carry a `Retry-After` header are retried once
+/// the requested time has passed, up to
+/// [`RetryPolicy::max_retry_after`](#method.max_retry_after).
+///
+/// Requests whose body is a reader or a stream cannot be replayed and are
+/// never retried.
+///
+/// ```rust,ignore
+/// let policy = RetryPolicy::new(5)
+///                  .backoff(Duration::from_millis(200), Duration::from_secs(5));
+/// let future = get(&url).retry(policy).send(evloop.handle());
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    base_delay: Duration,
+    honor_retry_after: bool,
+    max_attempts: u32,
+    max_delay: Duration,
+    max_retry_after: Duration,
+    predicate: Predicate
+}
+
+/// Sends a request and retries it according to a `RetryPolicy`.
+pub(crate) struct Retry {
+    attempts: u32,
+    method: Method,
+    policy: RetryPolicy,
+    remote: Remote,
+    session: Session,
+    state: State,
+    template: Option<Request>
+}
+
+enum State {
+    Sending(BoxFuture<Response, Error>),
+    Waiting(Timeout)
+}
+
+impl RetryPolicy {
+    /// Creates a new policy that makes up to `max_attempts` attempts in total,
+    /// with the default backoff and predicate.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            base_delay: DEFAULT_RETRY_BASE_DELAY,
+            honor_retry_after: false,
+            max_attempts,
+            max_delay: DEFAULT_RETRY_MAX_DELAY,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+            predicate: Arc::new(Self::is_transient)
+        }
+    }
+
+    /// Sets the delay before the first retry and the upper bound for the delay
+    /// between two attempts.
+    pub fn backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets whether 429 and 503 responses with a `Retry-After` header are
+    /// retried after the time the server asks for.
+    ///
+    /// These responses are retried regardless of the method and the
+    /// predicate, since the server did not process the request. They are
+    /// given up on if the server asks for a longer wait than
+    /// [`RetryPolicy::max_retry_after`](#method.max_retry_after).
+    /// Responses without the header are left to the predicate.
+    ///
+    /// Defaults to `false`.
+    pub fn honor_retry_after(mut self, honor: bool) -> Self {
+        self.honor_retry_after = honor;
+        self
+    }
+
+    /// Sets the longest wait a `Retry-After` header may ask for before the
+    /// response is given up on.
+    ///
+    /// This is independent of the backoff, since servers commonly ask for
+    /// longer waits than a backoff would choose.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RETRY_AFTER`](constant.DEFAULT_MAX_RETRY_AFTER.html).
+    pub fn max_retry_after(mut self, max: Duration) -> Self {
+        self.max_retry_after = max;
+        self
+    }
+
+    /// Sets the predicate that decides whether the outcome of an attempt
+    /// should be retried.
+    ///
+    /// The predicate is given the method of the request and either the
+    /// response or the error of the attempt. It replaces the default, so
+    /// combine it with [`RetryPolicy::is_transient`](#method.is_transient) to
+    /// extend the default instead.
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+            where F: Fn(&Method, Result<&Response, &Error>) -> bool + Send + Sync + 'static {
+        self.predicate = Arc::new(predicate);
+        self
+    }
+
+    /// Gets the maximum amount of attempts, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Computes a random delay before the given retry, starting at 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        let max = self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        jitter(max)
+    }
+
+    /// Computes the delay before the given retry, starting at 1, if the
+    /// outcome of the previous attempt should be retried.
+    pub fn next_delay(&self, retry: u32, method: &Method, outcome: Result<&Response, &Error>) -> Option<Duration> {
+        if self.honor_retry_after {
+            let requested = outcome.ok()
+                .filter(|response| matches!(response.status_code(), 429 | 503))
+                .and_then(Response::retry_after);
+            if let Some(delay) = requested {
+                return Some(delay).filter(|&delay| delay <= self.max_retry_after);
+            }
+        }
+        if self.should_retry(method, outcome) {
+            Some(self.delay(retry))
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether the outcome of an attempt should be retried.
+    pub fn should_retry(&self, method: &Method, outcome: Result<&Response, &Error>) -> bool {
+        (self.predicate)(method, outcome)
+    }
+
+    /// The default predicate.
+    ///
+    /// Retries requests with idempotent methods that failed with a 502, 503
+    /// or 504 response, a connection error, a reset connection or a timeout
+    /// other than the deadline.
+    pub fn is_transient(method: &Method, outcome: Result<&Response, &Error>) -> bool {
+        if !method.is_idempotent() {
+            return false;
+        }
+        match outcome {
+            Ok(response) => matches!(response.status_code(), 502..=504),
+            Err(Error::Connect(_)) |
+            Err(Error::ConnectTimeout(_)) |
+            Err(Error::DnsTimeout(_)) |
+            Err(Error::LowSpeed(_)) |
+            Err(Error::ReadTimeout(_)) |
+            Err(Error::Timeout(_)) => true,
+            Err(Error::Curl(err)) => {
+                err.is_got_nothing() || err.is_recv_error() || err.is_send_error()
+            },
+            Err(_) => false
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ATTEMPTS)
+    }
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(RetryPolicy))
+            .field("base_delay", &self.base_delay)
+            .field("honor_retry_after", &self.honor_retry_after)
+            .field("max_attempts", &self.max_attempts)
+            .field("max_delay", &self.max_delay)
+            .field("max_retry_after", &self.max_retry_after)
+            .finish()
+    }
+}
+
+impl Retry {
+    /// Starts sending the request through the given session.
+    ///
+    /// The delays between attempts are waited out on the event loop behind
+    /// `remote`, which the future must be polled on.
+    pub fn new(request: Request, policy: RetryPolicy, session: Session, remote: Remote) -> Self {
+        let method = request.method().clone();
+        let template = request.try_clone();
+        let state = State::Sending(request.send_once(&session));
+        Retry {
+            attempts: 1,
+            method,
+            policy,
+            remote,
+            session,
+            state,
+            template
+        }
+    }
+
+    /// Computes the delay before the next attempt, if there should be one.
+    fn next_delay(&self, outcome: Result<&Response, &Error>) -> Option<Duration> {
+        let cancelled = matches!(outcome, Err(&Error::Cancelled));
+        if cancelled || self.template.is_none() || self.attempts >= self.policy.max_attempts() {
+            return None;
+        }
+        let delay = self.policy.next_delay(self.attempts, &self.method, outcome)?;
+        // An attempt that starts after the deadline could only fail.
+        let deadline = self.template.as_ref().and_then(Request::deadline_at);
+        if deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+            return None;
+        }
+        Some(delay)
+    }
+}
+
+impl Future for Retry {
+    type Item = Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Response, Error> {
+        loop {
+            let next = match self.state {
+                State::Sending(ref mut future) => {
+                    let outcome = match future.poll() {
+                        Ok(Async::Ready(response)) => Ok(response),
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(error) => Err(error)
+                    };
+                    let delay = match self.next_delay(outcome.as_ref()) {
+                        Some(delay) => delay,
+                        None => return outcome.map(Async::Ready)
+                    };
+
+                    let handle = self.remote.handle().ok_or_else(|| {
+                        io::Error::other("retries must be driven by the event loop they were created on")
+                    })?;
+                    State::Waiting(Timeout::new(delay, &handle)?)
+                },
+                State::Waiting(ref mut timeout) => {
+                    if let Async::NotReady = timeout.poll()? {
+                        return Ok(Async::NotReady);
+                    }
+
+                    self.attempts += 1;
+                    let request = self.template.as_ref()
+                        .and_then(Request::try_clone)
+                        .expect("retried request without template");
+                    State::Sending(request.send_once(&self.session))
+                }
+            };
+            self.state = next;
+        }
+    }
+}
+
+/// Picks a random duration between zero and `max`.
+fn jitter(max: Duration) -> Duration {
+    let nanos = max.as_secs() * 1_000_000_000 + u64::from(max.subsec_nanos());
+    Duration::from_nanos(random_u64() % (nanos + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use tokio_core::reactor::Core;
+
+    use str::{get, post};
+    use test_server::{respond, serve};
+    use super::RetryPolicy;
+
+    /// Returns the URL of a server answering with 503 `failures` times, and
+    /// the counter of requests it received.
+    fn flaky_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let url = serve(move |_, stream| {
+            if server_count.fetch_add(1, Ordering::SeqCst) < failures {
+                respond(stream, "503 Service Unavailable", &[], b"");
+            } else {
+                respond(stream, "200 OK", &[], b"ok");
+            }
+        });
+        (url.into_string(), count)
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(max_attempts).backoff(Duration::from_millis(10), Duration::from_millis(50))
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let (url, count) = flaky_server(2);
+        let mut evloop = Core::new().unwrap();
+        let future = get(&url).retry(policy(3)).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let (url, count) = flaky_server(5);
+        let mut evloop = Core::new().unwrap();
+        let future = get(&url).retry(policy(2)).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+        assert_eq!(response.status_code(), 503);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn does_not_retry_post_by_default() {
+        let (url, count) = flaky_server(1);
+        let mut evloop = Core::new().unwrap();
+        let future = post(&url).body("data").retry(policy(3)).send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().status_code(), 503);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let future = post(&url)
+            .body("data")
+            .retry(policy(3).retry_if(|_, outcome| outcome.map(|r| r.status_code() == 503).unwrap_or(false)))
+            .send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().status_code(), 200);
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let server_count = count.clone();
+        let url = serve(move |_, stream| {
+            match server_count.fetch_add(1, Ordering::SeqCst) {
+                0 => respond(stream, "429 Too Many Requests", &[("Retry-After", "1")], b""),
+                1 => respond(stream, "503 Service Unavailable", &[("Retry-After", "3600")], b""),
+                _ => respond(stream, "200 OK", &[], b"ok")
+            }
+        }).into_string();
+
+        let mut evloop = Core::new().unwrap();
+        // The requested wait is not capped by the backoff.
+        let policy = RetryPolicy::new(5)
+            .backoff(Duration::from_millis(10), Duration::from_millis(50))
+            .honor_retry_after(true)
+            .max_retry_after(Duration::from_secs(60));
+        let started = Instant::now();
+        let future = post(&url).body("data").retry(policy).send(evloop.handle());
+        let response = evloop.run(future).unwrap();
+
+        // The second response asks for a longer wait than allowed.
+        assert_eq!(response.status_code(), 503);
+        assert_eq!(response.retry_after(), Some(Duration::from_secs(3600)));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let policy = policy(10);
+        for retry in 1..40 {
+            assert!(policy.delay(retry) <= Duration::from_millis(50));
+        }
+        assert!(policy.delay(1) <= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,640 @@
+//! The module that contains the code for downloading a resource in
+//! parallel segments.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::{Arc, Mutex};
+
+use {BoxFuture, Method};
+
+use curl::easy::Easy;
+use download::{content_range_start, validator, write_body, BodySink, Download, Target};
+use error::{Error, HttpError};
+use futures::{future, Async, Future, Poll};
+use request::Request;
+use response::{is_success, read_heads, HeadLines, Response};
+use retry::RetryPolicy;
+use tokio_core::reactor::{Handle, Remote, Timeout};
+use tokio_curl::Session;
+use transfer::Transfer;
+
+/// A checksum a downloaded file is verified against.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Checksum {
+    /// The CRC-32 of the file, as used by gzip and zip.
+    Crc32(u32)
+}
+
+/// Downloads a single resource with several concurrent range requests.
+///
+/// The size of the resource is learned with a `HEAD` request first. If the
+/// server does not tell it or does not accept byte ranges, the resource is
+/// downloaded with a single request instead, like
+/// [`Request::download_to`](struct.Request.html#method.download_to) does.
+///
+/// ```rust,ignore
+/// let future = SegmentedDownload::new(get(&url), "debian.iso")
+///     .segments(8)
+///     .checksum(Checksum::Crc32(0x3610a686))
+///     .start(evloop.handle());
+/// let download = evloop.run(future)?;
+/// ```
+pub struct SegmentedDownload {
+    checksum: Option<Checksum>,
+    path: PathBuf,
+    request: Request,
+    segment_retries: u32,
+    segments: u64
+}
+
+/// Downloads a single segment, retrying it where it left off.
+struct Segment {
+    attempts: u32,
+    end: u64,
+    next: u64,
+    policy: RetryPolicy,
+    remote: Remote,
+    request: Request,
+    retries: u32,
+    session: Session,
+    state: Option<Arc<Mutex<SegmentState>>>,
+    temp: PathBuf,
+    transfer: Option<Transfer>,
+    waiting: Option<Timeout>
+}
+
+/// The state shared between the cURL callbacks of a segment and its future.
+struct SegmentState {
+    body: Vec<u8>,
+    end: u64,
+    error: Option<io::Error>,
+    file: File,
+    heads: HeadLines,
+    next: u64,
+    started: bool
+}
+
+impl Checksum {
+    /// Computes the checksum of the same kind of the file at the given path.
+    fn compute(&self, path: &Path) -> io::Result<Checksum> {
+        let mut file = File::open(path)?;
+        match *self {
+            Checksum::Crc32(_) => {
+                let table = crc32_table();
+                let mut crc = !0u32;
+                let mut buf = [0; 64 * 1024];
+                loop {
+                    let read = file.read(&mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    for &byte in &buf[..read] {
+                        crc = table[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+                    }
+                }
+                Ok(Checksum::Crc32(!crc))
+            }
+        }
+    }
+
+    /// Checks the file at the given path and removes it if it does not match.
+    fn verify(&self, path: &Path) -> Result<(), Error> {
+        let actual = self.compute(path)?;
+        if actual == *self {
+            Ok(())
+        } else {
+            let _ = fs::remove_file(path);
+            Err(Error::ChecksumMismatch { actual, expected: *self })
+        }
+    }
+}
+
+impl Display for Checksum {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match *self {
+            Checksum::Crc32(crc) => write!(fmt, "crc32:{:08x}", crc)
+        }
+    }
+}
+
+impl SegmentedDownload {
+    /// Creates a download of the response to the given request to the file
+    /// at the given path.
+    ///
+    /// The request is sent once per segment and attempt, so its body must
+    /// not be a stream.
+    pub fn new<P: AsRef<Path>>(request: Request, path: P) -> Self {
+        SegmentedDownload {
+            checksum: None,
+            path: path.as_ref().to_owned(),
+            request,
+            segment_retries: 3,
+            segments: 4
+        }
+    }
+
+    /// Sets the checksum the downloaded file is verified against.
+    ///
+    /// The file is removed and the download fails with
+    /// [`Error::ChecksumMismatch`](enum.Error.html#variant.ChecksumMismatch)
+    /// if it does not match.
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    /// Sets how often a failed segment is requested again, continuing where
+    /// it left off.
+    ///
+    /// Only server errors and failed connections are retried, after the
+    /// backoff of the request's [`RetryPolicy`](struct.RetryPolicy.html)
+    /// or the default one.
+    ///
+    /// Defaults to 3.
+    pub fn segment_retries(mut self, retries: u32) -> Self {
+        self.segment_retries = retries;
+        self
+    }
+
+    /// Sets the number of concurrent range requests.
+    ///
+    /// Defaults to 4.
+    pub fn segments(mut self, segments: u32) -> Self {
+        self.segments = u64::from(segments.max(1));
+        self
+    }
+
+    /// Creates a new `Session` on the specified event loop and starts the
+    /// download through it.
+    pub fn start(self, h: Handle) -> BoxFuture<Download, Error> {
+        self.start_with_session(&Session::new(h.clone()), &h)
+    }
+
+    /// Starts the download through the given `Session`, which carries all
+    /// of its requests.
+    ///
+    /// The delays between the attempts at a segment are waited out on the
+    /// given event loop, which the future must be polled on.
+    pub fn start_with_session(self, session: &Session, h: &Handle) -> BoxFuture<Download, Error> {
+        let probe = match self.request.try_clone() {
+            Some(probe) => probe.with_method(Method::Head),
+            None => return Box::new(future::failed(Error::Io(body_not_clonable())))
+        };
+        let remote = h.remote().clone();
+        let session = session.clone();
+        let future = probe.send_once(&session).and_then(move |head| {
+            if !head.is_success() {
+                let err = HttpError::new(Method::Head, head.effective_url(), &head);
+                return Box::new(future::failed(Error::Http(Box::new(err)))) as BoxFuture<_, _>;
+            }
+            let accepts_ranges = head.header("Accept-Ranges").is_some_and(|ranges| ranges.contains("bytes"));
+            match head.headers().content_length() {
+                Some(len) if len > 0 && accepts_ranges => self.download_segments(head, len, &session, &remote),
+                _ => {
+                    let checksum = self.checksum;
+                    let path = self.path.clone();
+                    let future = self.request.download_to_with_session(&path, &session)
+                        .and_then(move |download| {
+                            match checksum {
+                                Some(checksum) => checksum.verify(&path).map(|_| download),
+                                None => Ok(download)
+                            }
+                        });
+                    Box::new(future)
+                }
+            }
+        });
+        Box::new(future)
+    }
+
+    /// Preallocates the file and downloads the segments into it.
+    fn download_segments(self, head: Response, len: u64, session: &Session, remote: &Remote) -> BoxFuture<Download, Error> {
+        let target = Target::new(&self.path);
+        if let Err(err) = File::create(&target.temp).and_then(|file| file.set_len(len)) {
+            return Box::new(future::failed(err.into()));
+        }
+
+        // Make sure all segments get the same representation of the resource,
+        // without following the redirects of the probe again.
+        let validator = validator(head.headers()).map(str::to_owned);
+        let url = head.effective_url();
+        let policy = self.request.retry_policy().cloned().unwrap_or_default();
+        let count = self.segments.min(len);
+        let mut segments = Vec::new();
+        for index in 0..count {
+            let request = match self.request.try_clone() {
+                Some(request) => request.with_url(url.clone()),
+                None => return Box::new(future::failed(Error::Io(body_not_clonable())))
+            };
+            let request = match validator {
+                Some(ref validator) => request.header("If-Range", validator),
+                None => request
+            };
+            segments.push(Segment {
+                attempts: 0,
+                end: len * (index + 1) / count - 1,
+                next: len * index / count,
+                policy: policy.clone(),
+                remote: remote.clone(),
+                request,
+                retries: self.segment_retries,
+                session: session.clone(),
+                state: None,
+                temp: target.temp.clone(),
+                transfer: None,
+                waiting: None
+            });
+        }
+
+        let checksum = self.checksum;
+        let future = future::join_all(segments).and_then(move |_| {
+            OpenOptions::new().write(true).open(&target.temp)?.sync_all()?;
+            if let Some(checksum) = checksum {
+                checksum.verify(&target.temp)?;
+            }
+            target.complete()?;
+            Ok(Download::new(len, target.path, head, 0))
+        });
+        Box::new(future)
+    }
+}
+
+impl Debug for SegmentedDownload {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(SegmentedDownload))
+            .field("checksum", &self.checksum)
+            .field("path", &self.path)
+            .field("request", &self.request)
+            .field("segment_retries", &self.segment_retries)
+            .field("segments", &self.segments)
+            .finish()
+    }
+}
+
+impl Segment {
+    /// Requests the rest of the segment.
+    fn start(&mut self) -> Result<(), Error> {
+        let request = self.request.try_clone()
+            .ok_or_else(body_not_clonable)?
+            .header("Range", &format!("bytes={}-{}", self.next, self.end));
+        let mut prepared = request.prepare()?;
+
+        let mut file = OpenOptions::new().write(true).open(&self.temp)?;
+        file.seek(SeekFrom::Start(self.next))?;
+        let state = Arc::new(Mutex::new(SegmentState {
+            body: Vec::new(),
+            end: self.end,
+            error: None,
+            file,
+            heads: HeadLines::default(),
+            next: self.next,
+            started: false
+        }));
+        let header_state = state.clone();
+        let write_state = state.clone();
+
+        read_heads(&mut prepared.easy, move |line| header_state.lock().unwrap().heads.push(line))?;
+        write_body(&mut prepared.easy, write_state)?;
+
+        self.state = Some(state);
+        self.transfer = Some(prepared.perform(&self.session));
+        Ok(())
+    }
+
+    /// Checks the outcome of a finished attempt.
+    fn finish(&mut self, res: Result<Easy, Error>) -> Result<(), Error> {
+        let state = self.state.take().expect("segment without state");
+        let transfer = self.transfer.take().expect("segment without transfer");
+        let mut state = state.lock().unwrap();
+        self.next = state.next;
+
+        let easy = match (res, state.error.take()) {
+            (_, Some(err)) => return Err(Error::Io(err)),
+            (Err(err), None) => return Err(err),
+            (Ok(easy), None) => easy
+        };
+        if !is_success(state.heads.status_code) {
+            let response = Response::new(easy, mem::take(&mut state.heads.lines), mem::take(&mut state.body))
+                .sent_to(self.request.url().clone());
+            let err = HttpError::new(self.request.method().clone(), response.effective_url(), &response);
+            transfer.recycle(response.into());
+            return Err(Error::Http(Box::new(err)));
+        }
+        transfer.recycle(easy);
+        if self.next <= self.end {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "segment ended early")));
+        }
+        Ok(())
+    }
+
+    /// Checks whether a failed attempt should be retried.
+    ///
+    /// Server errors and broken transfers are, while any other answer
+    /// would most likely be the same the next time.
+    fn should_retry(&self, err: &Error) -> bool {
+        if self.attempts >= self.retries {
+            return false;
+        }
+        match *err {
+            Error::Http(ref err) => err.status().is_server_error(),
+            Error::Io(ref err) => err.kind() == io::ErrorKind::UnexpectedEof,
+            Error::Curl(ref err) if err.is_partial_file() => true,
+            ref err => RetryPolicy::is_transient(self.request.method(), Err(err))
+        }
+    }
+}
+
+impl Future for Segment {
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<(), Error> {
+        loop {
+            if let Some(ref mut timeout) = self.waiting {
+                if let Async::NotReady = timeout.poll()? {
+                    return Ok(Async::NotReady);
+                }
+            }
+            self.waiting = None;
+            if self.transfer.is_none() {
+                self.start()?;
+            }
+            let res = match self.transfer.as_mut().unwrap().poll() {
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(easy)) => Ok(easy),
+                Err(err) => Err(err)
+            };
+            match self.finish(res) {
+                Ok(()) => return Ok(Async::Ready(())),
+                Err(ref err) if self.should_retry(err) => {},
+                Err(err) => return Err(err)
+            }
+
+            self.attempts += 1;
+            let handle = self.remote.handle().ok_or_else(|| {
+                io::Error::other("segments must be driven by the event loop they were created on")
+            })?;
+            self.waiting = Some(Timeout::new(self.policy.delay(self.attempts), &handle)?);
+        }
+    }
+}
+
+impl BodySink for SegmentState {
+    /// Writes a chunk of the segment to its place in the file.
+    ///
+    /// The bodies of error responses are kept for the error instead.
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if !is_success(self.heads.status_code) {
+            self.body.extend_from_slice(data);
+            return Ok(());
+        }
+        if !self.started {
+            if self.heads.status_code != 206 || content_range_start(&self.heads.headers()) != Some(self.next) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "server did not send the requested range"));
+            }
+            self.started = true;
+        }
+        if self.next + data.len() as u64 > self.end + 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "server sent more than the requested range"));
+        }
+        self.file.write_all(data)?;
+        self.next += data.len() as u64;
+        Ok(())
+    }
+
+    fn fail(&mut self, err: io::Error) {
+        self.error = Some(err);
+    }
+}
+
+/// Creates the error for requests whose body cannot be sent more than once.
+fn body_not_clonable() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the request body cannot be sent more than once")
+}
+
+/// Computes the lookup table for the reflected CRC-32 polynomial.
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::net::{Shutdown, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve, ServerRequest, TempPath};
+    use super::{Checksum, SegmentedDownload};
+
+    /// Answers a range request for the given content, cutting the first
+    /// answer short if `flaky` is set.
+    fn serve_range(request: &ServerRequest, stream: &mut TcpStream, content: &[u8], flaky: bool) {
+        if request.method == "HEAD" {
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\n\r\n", content.len());
+            let _ = stream.write_all(head.as_bytes());
+            return;
+        }
+        let range = request.header("Range").unwrap().trim_start_matches("bytes=");
+        let mut bounds = range.split('-').map(|bound| bound.parse::<usize>().unwrap());
+        let (start, end) = (bounds.next().unwrap(), bounds.next().unwrap());
+        let content_range = format!("bytes {}-{}/{}", start, end, content.len());
+        let headers = [("Content-Range", content_range.as_str()), ("ETag", "\"v1\"")];
+        if flaky {
+            let head = format!("HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: {}\r\n\r\n", end + 1 - start, content_range);
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&content[start..start + (end + 1 - start) / 2]);
+            let _ = stream.shutdown(Shutdown::Both);
+        } else {
+            respond(stream, "206 Partial Content", &headers, &content[start..=end]);
+        }
+    }
+
+    fn content() -> Vec<u8> {
+        (0..100_000u32).map(|n| (n % 251) as u8).collect()
+    }
+
+    #[test]
+    fn computes_crc32() {
+        let path = TempPath::new("crc32");
+        fs::write(&path, b"123456789").unwrap();
+        assert_eq!(Checksum::Crc32(0).compute(&path).unwrap(), Checksum::Crc32(0xcbf4_3926));
+    }
+
+    #[test]
+    fn downloads_segments_in_parallel() {
+        let ranges = Arc::new(AtomicUsize::new(0));
+        let counter = ranges.clone();
+        let url = serve(move |request, stream| {
+            if request.header("Range").is_some() {
+                assert_eq!(request.header("If-Range"), Some("\"v1\""));
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            serve_range(&request, stream, &content(), false)
+        });
+        let path = TempPath::new("segmented");
+        let mut evloop = Core::new().unwrap();
+
+        let crc = Checksum::Crc32(0).compute(&{
+            let expected = TempPath::new("segmented-expected");
+            fs::write(&expected, content()).unwrap();
+            expected
+        }).unwrap();
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .segments(4)
+            .checksum(crc)
+            .start(evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.total_bytes(), 100_000);
+        assert_eq!(ranges.load(Ordering::SeqCst), 4);
+        assert_eq!(fs::read(&path).unwrap(), content());
+    }
+
+    #[test]
+    fn retries_segment_where_it_left_off() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = serve(move |request, stream| {
+            let flaky = request.header("Range").is_some() && counter.fetch_add(1, Ordering::SeqCst) == 0;
+            serve_range(&request, stream, &content(), flaky)
+        });
+        let path = TempPath::new("segmented-retry");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .segments(2)
+            .start(evloop.handle());
+        evloop.run(future).unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(fs::read(&path).unwrap(), content());
+    }
+
+    #[test]
+    fn retries_server_errors_only() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let url = serve(move |request, stream| {
+            if request.header("Range").is_none() {
+                return serve_range(&request, stream, &content(), false);
+            }
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => respond(stream, "503 Service Unavailable", &[], b""),
+                1 => respond(stream, "403 Forbidden", &[], b"denied"),
+                _ => serve_range(&request, stream, &content(), false)
+            }
+        });
+        let path = TempPath::new("segmented-errors");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .segments(1)
+            .start(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::Http(err)) => assert_eq!(err.status().as_u16(), 403),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn requests_segments_from_effective_url() {
+        let redirected = Arc::new(AtomicUsize::new(0));
+        let counter = redirected.clone();
+        let url = serve(move |request, stream| {
+            if request.path.starts_with("/old") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                respond(stream, "301 Moved Permanently", &[("Location", "/new")], b"");
+            } else {
+                serve_range(&request, stream, &content(), false)
+            }
+        });
+        let path = TempPath::new("segmented-redirect");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.join("old").unwrap().as_str()), &path)
+            .start(evloop.handle());
+        evloop.run(future).unwrap();
+        assert_eq!(redirected.load(Ordering::SeqCst), 1);
+        assert_eq!(fs::read(&path).unwrap(), content());
+    }
+
+    #[test]
+    fn falls_back_without_range_support() {
+        let ranges = Arc::new(AtomicUsize::new(0));
+        let counter = ranges.clone();
+        let url = serve(move |request, stream| {
+            if request.header("Range").is_some() {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            if request.method == "HEAD" {
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", content().len());
+                let _ = stream.write_all(head.as_bytes());
+            } else {
+                respond(stream, "200 OK", &[], &content())
+            }
+        });
+        let path = TempPath::new("segmented-fallback");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .start(evloop.handle());
+        let download = evloop.run(future).unwrap();
+        assert_eq!(download.response().status_code(), 200);
+        assert_eq!(ranges.load(Ordering::SeqCst), 0);
+        assert_eq!(fs::read(&path).unwrap(), content());
+    }
+
+    #[test]
+    fn splits_small_resources_into_fewer_segments() {
+        let ranges = Arc::new(AtomicUsize::new(0));
+        let counter = ranges.clone();
+        let url = serve(move |request, stream| {
+            if request.header("Range").is_some() {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            serve_range(&request, stream, b"abc", false)
+        });
+        let path = TempPath::new("segmented-small");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .segments(8)
+            .start(evloop.handle());
+        evloop.run(future).unwrap();
+        assert_eq!(ranges.load(Ordering::SeqCst), 3);
+        assert_eq!(fs::read(&path).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let url = serve(|request, stream| serve_range(&request, stream, &content(), false));
+        let path = TempPath::new("segmented-mismatch");
+        let mut evloop = Core::new().unwrap();
+
+        let future = SegmentedDownload::new(get(url.as_str()), &path)
+            .checksum(Checksum::Crc32(0))
+            .start(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::ChecksumMismatch { expected, .. }) => assert_eq!(expected, Checksum::Crc32(0)),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert!(!path.exists());
+    }
+}
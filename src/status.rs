@@ -0,0 +1,253 @@
+//! The module that contains the HTTP status code type.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// An HTTP status code.
+///
+/// ```rust,ignore
+/// let status = response.status();
+/// if status == StatusCode::NOT_FOUND {
+///     // ...
+/// } else if status.is_server_error() {
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct StatusCode(pub(crate) u16);
+
+impl StatusCode {
+    /// 100 Continue
+    pub const CONTINUE: StatusCode = StatusCode(100);
+    /// 101 Switching Protocols
+    pub const SWITCHING_PROTOCOLS: StatusCode = StatusCode(101);
+    /// 200 OK
+    pub const OK: StatusCode = StatusCode(200);
+    /// 201 Created
+    pub const CREATED: StatusCode = StatusCode(201);
+    /// 202 Accepted
+    pub const ACCEPTED: StatusCode = StatusCode(202);
+    /// 204 No Content
+    pub const NO_CONTENT: StatusCode = StatusCode(204);
+    /// 206 Partial Content
+    pub const PARTIAL_CONTENT: StatusCode = StatusCode(206);
+    /// 301 Moved Permanently
+    pub const MOVED_PERMANENTLY: StatusCode = StatusCode(301);
+    /// 302 Found
+    pub const FOUND: StatusCode = StatusCode(302);
+    /// 303 See Other
+    pub const SEE_OTHER: StatusCode = StatusCode(303);
+    /// 304 Not Modified
+    pub const NOT_MODIFIED: StatusCode = StatusCode(304);
+    /// 307 Temporary Redirect
+    pub const TEMPORARY_REDIRECT: StatusCode = StatusCode(307);
+    /// 308 Permanent Redirect
+    pub const PERMANENT_REDIRECT: StatusCode = StatusCode(308);
+    /// 400 Bad Request
+    pub const BAD_REQUEST: StatusCode = StatusCode(400);
+    /// 401 Unauthorized
+    pub const UNAUTHORIZED: StatusCode = StatusCode(401);
+    /// 403 Forbidden
+    pub const FORBIDDEN: StatusCode = StatusCode(403);
+    /// 404 Not Found
+    pub const NOT_FOUND: StatusCode = StatusCode(404);
+    /// 405 Method Not Allowed
+    pub const METHOD_NOT_ALLOWED: StatusCode = StatusCode(405);
+    /// 408 Request Timeout
+    pub const REQUEST_TIMEOUT: StatusCode = StatusCode(408);
+    /// 409 Conflict
+    pub const CONFLICT: StatusCode = StatusCode(409);
+    /// 410 Gone
+    pub const GONE: StatusCode = StatusCode(410);
+    /// 412 Precondition Failed
+    pub const PRECONDITION_FAILED: StatusCode = StatusCode(412);
+    /// 413 Content Too Large
+    pub const PAYLOAD_TOO_LARGE: StatusCode = StatusCode(413);
+    /// 415 Unsupported Media Type
+    pub const UNSUPPORTED_MEDIA_TYPE: StatusCode = StatusCode(415);
+    /// 416 Range Not Satisfiable
+    pub const RANGE_NOT_SATISFIABLE: StatusCode = StatusCode(416);
+    /// 422 Unprocessable Content
+    pub const UNPROCESSABLE_ENTITY: StatusCode = StatusCode(422);
+    /// 429 Too Many Requests
+    pub const TOO_MANY_REQUESTS: StatusCode = StatusCode(429);
+    /// 500 Internal Server Error
+    pub const INTERNAL_SERVER_ERROR: StatusCode = StatusCode(500);
+    /// 501 Not Implemented
+    pub const NOT_IMPLEMENTED: StatusCode = StatusCode(501);
+    /// 502 Bad Gateway
+    pub const BAD_GATEWAY: StatusCode = StatusCode(502);
+    /// 503 Service Unavailable
+    pub const SERVICE_UNAVAILABLE: StatusCode = StatusCode(503);
+    /// 504 Gateway Timeout
+    pub const GATEWAY_TIMEOUT: StatusCode = StatusCode(504);
+
+    /// Creates a status code from the given number.
+    ///
+    /// Returns `None` if the number does not have three digits.
+    pub fn from_u16(code: u16) -> Option<StatusCode> {
+        if (100..=999).contains(&code) {
+            Some(StatusCode(code))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the status code as number.
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
+
+    /// Gets the reason phrase the status code is registered with, e.g.
+    /// `Not Found` for 404.
+    ///
+    /// Returns `None` for unregistered status codes.
+    pub fn canonical_reason(&self) -> Option<&'static str> {
+        let reason = match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            102 => "Processing",
+            103 => "Early Hints",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            207 => "Multi-Status",
+            208 => "Already Reported",
+            226 => "IM Used",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            305 => "Use Proxy",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Content",
+            423 => "Locked",
+            424 => "Failed Dependency",
+            425 => "Too Early",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            506 => "Variant Also Negotiates",
+            507 => "Insufficient Storage",
+            508 => "Loop Detected",
+            510 => "Not Extended",
+            511 => "Network Authentication Required",
+            _ => return None
+        };
+        Some(reason)
+    }
+
+    /// Checks whether the status code is a 4xx one.
+    pub fn is_client_error(&self) -> bool {
+        (400..=499).contains(&self.0)
+    }
+
+    /// Checks whether the status code is a 1xx one.
+    pub fn is_informational(&self) -> bool {
+        (100..=199).contains(&self.0)
+    }
+
+    /// Checks whether the status code is a 3xx one.
+    pub fn is_redirection(&self) -> bool {
+        (300..=399).contains(&self.0)
+    }
+
+    /// Checks whether the status code is a 5xx one.
+    pub fn is_server_error(&self) -> bool {
+        (500..=599).contains(&self.0)
+    }
+
+    /// Checks whether the status code is a 2xx one.
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&self.0)
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self.canonical_reason() {
+            Some(reason) => write!(fmt, "{} {}", self.0, reason),
+            None => write!(fmt, "{}", self.0)
+        }
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> Self {
+        status.0
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_core::reactor::Core;
+
+    use str::get;
+    use test_server::{respond, serve};
+    use super::StatusCode;
+
+    #[test]
+    fn classifies_status_codes() {
+        assert!(StatusCode::CONTINUE.is_informational());
+        assert!(StatusCode::NO_CONTENT.is_success());
+        assert!(StatusCode::NOT_MODIFIED.is_redirection());
+        assert!(StatusCode::TOO_MANY_REQUESTS.is_client_error());
+        assert!(StatusCode::BAD_GATEWAY.is_server_error());
+        assert_eq!(StatusCode::from_u16(42), None);
+        assert_eq!(StatusCode::from_u16(404), Some(StatusCode::NOT_FOUND));
+        assert_eq!(StatusCode::NOT_FOUND.to_string(), "404 Not Found");
+        assert_eq!(StatusCode::from_u16(599).unwrap().to_string(), "599");
+    }
+
+    #[test]
+    fn keeps_server_reason_phrase() {
+        let url = serve(|_, stream| respond(stream, "404 Nothing To See Here", &[], b""));
+        let mut evloop = Core::new().unwrap();
+
+        let response = evloop.run(get(url.as_str()).send(evloop.handle())).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.reason(), Some("Nothing To See Here"));
+
+        let response = evloop.run(get(url.as_str()).send_streaming(evloop.handle())).unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.reason(), Some("Nothing To See Here"));
+    }
+}
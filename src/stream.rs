@@ -0,0 +1,415 @@
+//! The module that contains the code for streaming response bodies.
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::mem;
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use curl::easy::WriteError;
+use error::Error;
+use futures::{Async, Future, Poll, Stream};
+use futures::task::{self, Task};
+use header::HeaderMap;
+use mime::Mime;
+use response::{is_success, parse_headers, parse_reason, parse_retry_after, read_heads, HeadLine};
+use status::StatusCode;
+use tokio_curl::Session;
+use transfer::{Prepared, Transfer};
+
+/// The amount of response body bytes that are buffered before a streaming
+/// transfer is paused.
+///
+/// The transfer is resumed once the consumer of the
+/// [`BodyStream`](struct.BodyStream.html) has drained the buffer to half
+/// of this size.
+pub const STREAM_BUFFER_SIZE: usize = 256 * 1024;
+
+/// A future that resolves to a `StreamingResponse` once the response
+/// headers have been received.
+///
+/// Returned by [`Request::send_streaming`](struct.Request.html#method.send_streaming).
+pub struct SendStreaming {
+    inner: Option<Result<BodyStream, Error>>
+}
+
+/// Represents an HTTP response whose body has not been received yet.
+pub struct StreamingResponse {
+    body: BodyStream,
+    headers: HeaderMap,
+    reason: Option<String>,
+    status_code: u16
+}
+
+/// A `Stream` over the chunks of a response body, as they arrive.
+///
+/// The stream must be polled on the event loop the `Session` used to
+/// send the request runs on.
+///
+/// Once [`STREAM_BUFFER_SIZE`](constant.STREAM_BUFFER_SIZE.html) bytes are
+/// buffered, the transfer is paused until the consumer catches up, so a
+/// slow consumer slows down the transfer instead of piling up the body in
+/// memory.
+pub struct BodyStream {
+    state: Arc<Mutex<State>>,
+    transfer: Option<Transfer>
+}
+
+/// The state shared between the cURL callbacks and the consumer.
+#[derive(Default)]
+struct State {
+    buffered: usize,
+    chunks: VecDeque<Bytes>,
+    has_location: bool,
+    headers: Vec<String>,
+    headers_done: bool,
+    paused: bool,
+    reason: Option<String>,
+    status_code: u16,
+    task: Option<Task>
+}
+
+impl SendStreaming {
+    /// Configures the callbacks on the given handle and starts the transfer.
+    pub(crate) fn new(mut prepared: Prepared, follow_redirects: bool, session: &Session) -> Self {
+        let easy = &mut prepared.easy;
+        let state = Arc::new(Mutex::new(State::default()));
+        let header_state = state.clone();
+        let write_state = state.clone();
+
+        let config_res = read_heads(easy, move |line| {
+            let mut state = header_state.lock().unwrap();
+            match line {
+                HeadLine::Status(line, status_code) => {
+                    state.status_code = status_code;
+                    state.reason = parse_reason(line);
+                    state.has_location = false;
+                    // Only the headers of the final response are kept.
+                    state.headers.clear();
+                },
+                HeadLine::End => {
+                    // Headers of informational responses and redirects cURL is going to
+                    // follow are not the ones the user is interested in.
+                    let redirect = follow_redirects &&
+                                   state.has_location &&
+                                   (300..=399).contains(&state.status_code);
+                    if state.status_code >= 200 && !redirect {
+                        state.headers_done = true;
+                        state.notify();
+                    }
+                },
+                HeadLine::Header(line) => {
+                    if line.to_lowercase().starts_with("location:") {
+                        state.has_location = true;
+                    }
+                    state.headers.push(line.to_owned());
+                }
+            }
+        }).and_then(|_| easy.write_function(move |data| {
+            let mut state = write_state.lock().unwrap();
+            if state.buffered >= STREAM_BUFFER_SIZE {
+                // cURL hands us the same data again once the transfer is resumed.
+                state.paused = true;
+                return Err(WriteError::Pause);
+            }
+            state.buffered += data.len();
+            state.chunks.push_back(Bytes::from(data));
+            state.notify();
+            Ok(data.len())
+        }));
+
+        match config_res {
+            Ok(_) => {
+                let body = BodyStream {
+                    state,
+                    transfer: Some(prepared.perform(session))
+                };
+                SendStreaming { inner: Some(Ok(body)) }
+            },
+            Err(error) => Self::failed(error.into())
+        }
+    }
+
+    /// Creates a future that immediately fails with the given error.
+    pub(crate) fn failed(error: Error) -> Self {
+        SendStreaming { inner: Some(Err(error)) }
+    }
+}
+
+impl Future for SendStreaming {
+    type Item = StreamingResponse;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<StreamingResponse, Error> {
+        let mut body = self.inner.take().expect("Cannot poll SendStreaming twice.")?;
+
+        let finished = match body.poll_transfer() {
+            Ok(Async::Ready(())) => true,
+            Ok(Async::NotReady) => false,
+            Err(error) => return Err(error)
+        };
+
+        let ready = {
+            let mut state = body.state.lock().unwrap();
+            if finished || state.headers_done || !state.chunks.is_empty() {
+                Some((mem::take(&mut state.headers), state.reason.take(), state.status_code))
+            } else {
+                state.task = Some(task::current());
+                None
+            }
+        };
+
+        match ready {
+            Some((headers, reason, status_code)) => Ok(Async::Ready(StreamingResponse {
+                body,
+                headers: parse_headers(headers),
+                reason,
+                status_code
+            })),
+            None => {
+                self.inner = Some(Ok(body));
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl Debug for SendStreaming {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(SendStreaming))
+            .field("failed", &self.inner.as_ref().is_some_and(|res| res.is_err()))
+            .finish()
+    }
+}
+
+impl StreamingResponse {
+    /// Consumes the response and returns the stream over the body chunks.
+    pub fn body(self) -> BodyStream {
+        self.body
+    }
+
+    /// Retreives the content type, if there is one.
+    ///
+    /// This function also returns none if there has been an error parsing
+    /// the mime type.
+    pub fn content_type(&self) -> Option<Mime> {
+        self.headers.content_type()
+    }
+
+    /// Attempts to get a single header value.
+    ///
+    /// The name is matched case-insensitively. If there are multiple headers
+    /// with the same name, this method returns the first one. If you need to
+    /// get access to the other values, use
+    /// [`StreamingResponse::headers()`](struct.StreamingResponse.html#method.headers).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// Gets all response headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Checks whether the returned status code represents a success
+    /// (HTTP status code 2xx) or not.
+    pub fn is_success(&self) -> bool {
+        is_success(self.status_code)
+    }
+
+    /// Gets how long the server asks the client to wait before sending the
+    /// next request, as told by the `Retry-After` header.
+    ///
+    /// See [`Response::retry_after`](struct.Response.html#method.retry_after)
+    /// for more information.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.header("Retry-After").and_then(parse_retry_after)
+    }
+
+    /// Gets the reason phrase the server sent along with the status code.
+    ///
+    /// See [`Response::reason`](struct.Response.html#method.reason) for more
+    /// information.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Gets the response status code.
+    pub fn status(&self) -> StatusCode {
+        StatusCode(self.status_code)
+    }
+
+    /// Gets the response status code as number.
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+}
+
+impl Debug for StreamingResponse {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(StreamingResponse))
+            .field("headers", &self.headers)
+            .field("reason", &self.reason)
+            .field("status_code", &self.status_code)
+            .finish()
+    }
+}
+
+impl BodyStream {
+    /// Checks whether the transfer has finished.
+    fn poll_transfer(&mut self) -> Poll<(), Error> {
+        let res = match self.transfer {
+            Some(ref mut transfer) => transfer.poll(),
+            None => return Ok(Async::Ready(()))
+        };
+        if let Ok(Async::NotReady) = res {
+            return Ok(Async::NotReady);
+        }
+        let transfer = self.transfer.take().unwrap();
+        res.map(|easy| {
+            if let Async::Ready(easy) = easy {
+                transfer.recycle(easy);
+            }
+            Async::Ready(())
+        })
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        loop {
+            let (chunk, resume) = {
+                let mut state = self.state.lock().unwrap();
+                let chunk = state.chunks.pop_front();
+                if let Some(ref chunk) = chunk {
+                    state.buffered -= chunk.len();
+                }
+                let resume = state.paused && state.buffered <= STREAM_BUFFER_SIZE / 2;
+                if resume {
+                    state.paused = false;
+                }
+                (chunk, resume)
+            };
+            if resume {
+                if let Some(ref mut transfer) = self.transfer {
+                    transfer.resume();
+                }
+            }
+            if let Some(chunk) = chunk {
+                return Ok(Async::Ready(Some(chunk)));
+            }
+
+            if let Async::Ready(()) = self.poll_transfer()? {
+                // The last chunks may have arrived together with the completion.
+                if self.state.lock().unwrap().chunks.is_empty() {
+                    return Ok(Async::Ready(None));
+                }
+                continue;
+            }
+
+            let mut state = self.state.lock().unwrap();
+            if state.chunks.is_empty() {
+                state.task = Some(task::current());
+                return Ok(Async::NotReady);
+            }
+        }
+    }
+}
+
+impl Debug for BodyStream {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let state = self.state.lock().unwrap();
+        fmt.debug_struct(stringify!(BodyStream))
+            .field("buffered", &state.buffered)
+            .field("finished", &self.transfer.is_none())
+            .field("paused", &state.paused)
+            .finish()
+    }
+}
+
+impl State {
+    /// Wakes up the task waiting for new data, if there is one.
+    fn notify(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::{Future, Stream};
+    use tokio_core::reactor::{Core, Timeout};
+
+    use str::get;
+    use test_server::{respond, serve};
+    use super::STREAM_BUFFER_SIZE;
+
+    #[test]
+    fn streams_body_in_chunks() {
+        const LEN: usize = 8 * 1024 * 1024;
+        let url = serve(|_, stream| respond(stream, "200 OK", &[("X-Test", "yes")], &vec![b'a'; LEN]));
+
+        let mut evloop = Core::new().unwrap();
+        let handle = evloop.handle();
+        let response = evloop.run(get(url.as_str()).send_streaming(handle.clone())).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header("X-Test"), Some("yes"));
+
+        let mut chunks = 0;
+        let mut received = 0;
+        let future = response.body().for_each(|chunk| {
+            chunks += 1;
+            received += chunk.len();
+            // Be a slow consumer every now and then
+            let delay = if chunks % 16 == 0 { 1 } else { 0 };
+            Timeout::new(Duration::from_millis(delay), &handle).unwrap().map_err(Into::into)
+        });
+        evloop.run(future).unwrap();
+
+        assert_eq!(received, LEN);
+        assert!(chunks > 1);
+    }
+
+    #[test]
+    fn pauses_transfer_while_consumer_does_not_poll() {
+        const LEN: usize = 8 * 1024 * 1024;
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], &vec![b'a'; LEN]));
+
+        let mut evloop = Core::new().unwrap();
+        let handle = evloop.handle();
+        let response = evloop.run(get(url.as_str()).send_streaming(handle.clone())).unwrap();
+        let body = response.body();
+        let state = body.state.clone();
+
+        // Keep the transfer running on the event loop without taking any chunks.
+        evloop.run(Timeout::new(Duration::from_millis(500), &handle).unwrap()).unwrap();
+        let buffered = state.lock().unwrap().buffered;
+        assert!(buffered <= STREAM_BUFFER_SIZE + 64 * 1024, "buffered {} bytes", buffered);
+        assert!(state.lock().unwrap().paused);
+
+        let received = evloop.run(body.fold(0, |received, chunk| Ok::<_, ::Error>(received + chunk.len()))).unwrap();
+        assert_eq!(received, LEN);
+    }
+
+    #[test]
+    fn empty_body_ends_stream() {
+        let url = serve(|_, stream| respond(stream, "204 No Content", &[], &[]));
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(url.as_str())
+            .send_streaming(evloop.handle())
+            .and_then(|response| {
+                assert_eq!(response.status_code(), 204);
+                response.body().collect()
+            });
+        assert!(evloop.run(future).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! A minimal HTTP/1.1 server for testing against, since we cannot rely on
+//! having network access in tests.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+use std::sync::Arc;
+use std::thread;
+
+use url::Url;
+
+/// A request received by the test server.
+#[derive(Debug)]
+pub struct ServerRequest {
+    pub body: Vec<u8>,
+    pub headers: Vec<(String, String)>,
+    pub method: String,
+    pub path: String
+}
+
+/// A path in the temporary directory that no other test or test run uses.
+///
+/// The file and the files a download leaves next to it are removed when
+/// the path is dropped, even if the test fails.
+pub struct TempPath(PathBuf);
+
+impl ServerRequest {
+    /// Gets the first header with the given name, case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|kvp| kvp.0.eq_ignore_ascii_case(name))
+            .map(|kvp| kvp.1.as_str())
+    }
+}
+
+impl TempPath {
+    /// Creates a path for the test with the given name.
+    pub fn new(name: &str) -> Self {
+        TempPath(env::temp_dir().join(format!("tokio-request-{}-{}", process::id(), name)))
+    }
+
+    /// Gets the path of the file with the given suffix next to this one.
+    pub fn with_suffix(&self, suffix: &str) -> PathBuf {
+        let mut path = OsString::from(self.0.as_os_str());
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        for suffix in &["", ".part", ".part.validator"] {
+            let _ = fs::remove_file(self.with_suffix(suffix));
+        }
+    }
+}
+
+/// Starts a server on a random local port that answers every request with
+/// the given handler and returns the base URL it is reachable under.
+///
+/// Each connection is handled on its own thread and may carry multiple
+/// requests.
+pub fn serve<F>(handler: F) -> Url
+        where F: Fn(ServerRequest, &mut TcpStream) + Send + Sync + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let handler = Arc::new(handler);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => return
+            };
+            let handler = handler.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while let Some(request) = read_request(&mut reader) {
+                    handler(request, &mut stream);
+                }
+            });
+        }
+    });
+
+    url
+}
+
+/// Writes a complete response with the given status, headers and body.
+pub fn respond(stream: &mut TcpStream, status: &str, headers: &[(&str, &str)], body: &[u8]) {
+    let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n", status, body.len());
+    for &(name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(body);
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<ServerRequest> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        let mut kvp = line.splitn(2, ':');
+        let name = kvp.next()?.trim().to_owned();
+        let value = kvp.next().unwrap_or("").trim().to_owned();
+        headers.push((name, value));
+    }
+
+    let mut request = ServerRequest {
+        body: Vec::new(),
+        headers,
+        method,
+        path
+    };
+    if request.header("Expect").is_some_and(|e| e.eq_ignore_ascii_case("100-continue")) {
+        reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n").ok()?;
+    }
+    if let Some(len) = request.header("Content-Length").and_then(|l| l.parse::<usize>().ok()) {
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).ok()?;
+        request.body = body;
+    } else if request.header("Transfer-Encoding").is_some_and(|te| te.eq_ignore_ascii_case("chunked")) {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).ok()?;
+            let size = usize::from_str_radix(size.trim(), 16).ok()?;
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk).ok()?;
+            if size == 0 {
+                break;
+            }
+            request.body.extend_from_slice(&chunk[..size]);
+        }
+    }
+    Some(request)
+}
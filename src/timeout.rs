@@ -0,0 +1,452 @@
+//! The module that contains the timeouts of a transfer.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use curl;
+use curl::easy::{Easy, List};
+use curl_sys;
+use error::Error;
+use futures::{Async, Future, Poll};
+use futures::sync::oneshot;
+
+/// The time cURL allows for connecting unless told otherwise.
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 300;
+
+/// The result of a host name lookup, or `None` if it took too long.
+type LookupResult = Option<io::Result<Vec<SocketAddr>>>;
+
+/// The timeouts of a transfer, used to enforce the ones cURL lacks and to
+/// tell apart which one expired.
+#[derive(Clone, Debug)]
+pub(crate) struct Timeouts {
+    /// The maximum time establishing a connection may take.
+    pub connect: Option<Duration>,
+    /// Whether the total timeout has been shortened to meet a deadline.
+    pub deadline: bool,
+    /// The maximum time no bytes may be transferred once connected.
+    pub read_idle: Option<Duration>,
+    /// The maximum time the whole transfer may take.
+    pub total: Option<Duration>,
+    observed: Arc<Mutex<Observed>>,
+    prepared: Instant
+}
+
+/// What the watchdog has seen of a running transfer.
+#[derive(Debug, Default)]
+struct Observed {
+    /// When cURL last reported progress, which it only does once connected.
+    last_report: Option<Instant>,
+    /// Whether the watchdog aborted the transfer for being idle.
+    stalled: bool
+}
+
+/// Enforces the read timeout from the progress callback, and keeps track of
+/// when cURL reported progress.
+pub(crate) struct Watchdog {
+    last_activity: Option<Instant>,
+    last_progress: (f64, f64),
+    observed: Arc<Mutex<Observed>>,
+    read_idle: Option<Duration>
+}
+
+/// Resolves the host name of a request on a separate thread, since cURL
+/// cannot limit the time a lookup takes.
+pub(crate) struct Lookup {
+    host: String,
+    port: u16,
+    result: oneshot::Receiver<LookupResult>
+}
+
+impl Timeouts {
+    /// Creates the timeouts of a request that is about to be sent, with the
+    /// total timeout shortened to meet the given deadline.
+    pub fn new(total: Option<Duration>, deadline: Option<Instant>) -> Result<Self, Error> {
+        let prepared = Instant::now();
+        let (total, deadline) = match (total, deadline) {
+            (_, Some(deadline)) if deadline <= prepared => {
+                return Err(Error::Deadline(curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT)));
+            },
+            (Some(total), Some(deadline)) if prepared + total <= deadline => (Some(total), false),
+            (_, Some(deadline)) => (Some(deadline - prepared), true),
+            (total, None) => (total, false)
+        };
+        Ok(Timeouts {
+            connect: None,
+            deadline,
+            read_idle: None,
+            total,
+            observed: Arc::default(),
+            prepared
+        })
+    }
+
+    /// Configures the given handle with the time that is left of the total
+    /// timeout, right before the transfer starts.
+    pub fn arm(&self, easy: &mut Easy) -> Result<(), Error> {
+        let total = match self.total {
+            Some(total) => total,
+            None => return Ok(())
+        };
+        // cURL counts in whole milliseconds, and treats 0 as no timeout at
+        // all. Rounding up keeps it from expiring before the total timeout.
+        match total.checked_sub(self.prepared.elapsed()) {
+            Some(left) if left > Duration::from_secs(0) => {
+                let millis = (left.as_nanos() as u64).div_ceil(1_000_000);
+                easy.timeout(Duration::from_millis(millis)).map_err(Into::into)
+            },
+            _ => Err(self.expired(curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT)))
+        }
+    }
+
+    /// Creates the watchdog for a transfer with these timeouts.
+    pub fn watchdog(&self) -> Watchdog {
+        Watchdog {
+            last_activity: None,
+            last_progress: (0.0, 0.0),
+            observed: self.observed.clone(),
+            read_idle: self.read_idle
+        }
+    }
+
+    /// Converts a cURL error into the error of the timeout that expired, if
+    /// one did.
+    pub fn classify(&self, err: curl::Error) -> Error {
+        self.classify_at(err, Instant::now())
+    }
+
+    /// Classifies the given error as if it occured at the given point in time.
+    ///
+    /// cURL reports its total, connect and low speed timeouts with the same
+    /// error code. The total timeout is measured from when the request was
+    /// prepared, which is before cURL starts its own clock, so it has passed
+    /// whenever cURL enforced it. cURL reports progress right before checking
+    /// the low speed limit, but never while connecting, which takes at least
+    /// as long as the connect timeout to time out.
+    fn classify_at(&self, err: curl::Error, now: Instant) -> Error {
+        let observed = self.observed.lock().unwrap();
+        if err.is_aborted_by_callback() && observed.stalled {
+            return Error::ReadTimeout(err);
+        }
+        if !err.is_operation_timedout() {
+            return Error::from(err);
+        }
+        if self.total.is_some_and(|total| now.duration_since(self.prepared) >= total) {
+            return self.expired(err);
+        }
+
+        let connect = self.connect.unwrap_or(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS));
+        match observed.last_report {
+            Some(last_report) if now.duration_since(last_report) < connect => Error::LowSpeed(err),
+            _ => Error::ConnectTimeout(err)
+        }
+    }
+
+    /// Wraps the given error into the one for an expired total timeout.
+    fn expired(&self, err: curl::Error) -> Error {
+        if self.deadline {
+            Error::Deadline(err)
+        } else {
+            Error::Timeout(err)
+        }
+    }
+}
+
+impl Watchdog {
+    /// Checks the transfer given the number of bytes received and sent so
+    /// far, returning `false` once it has been idle for too long.
+    pub fn check(&mut self, downloaded: f64, uploaded: f64) -> bool {
+        self.check_at(downloaded, uploaded, Instant::now())
+    }
+
+    /// Checks the transfer as if it was the given point in time.
+    fn check_at(&mut self, downloaded: f64, uploaded: f64, now: Instant) -> bool {
+        let mut observed = self.observed.lock().unwrap();
+        observed.last_report = Some(now);
+
+        // The first report comes in once the connection is established.
+        let last_activity = *self.last_activity.get_or_insert(now);
+        if (downloaded, uploaded) != self.last_progress {
+            self.last_progress = (downloaded, uploaded);
+            self.last_activity = Some(now);
+        } else if self.read_idle.is_some_and(|idle| now.duration_since(last_activity) >= idle) {
+            observed.stalled = true;
+            return false;
+        }
+        true
+    }
+}
+
+impl Lookup {
+    /// Starts resolving the given host name, giving up after the given
+    /// timeout.
+    pub fn new(host: &str, port: u16, timeout: Duration) -> Self {
+        let name = (host.to_owned(), port);
+        Self::with_resolver(host, port, timeout, move || {
+            name.to_socket_addrs().map(Iterator::collect)
+        })
+    }
+
+    /// Starts resolving the given host name through the given function.
+    ///
+    /// The lookup keeps running in the background after timing out.
+    fn with_resolver<F>(host: &str, port: u16, timeout: Duration, resolve: F) -> Self
+            where F: FnOnce() -> io::Result<Vec<SocketAddr>> + Send + 'static {
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            let (resolved_tx, resolved_rx) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = resolved_tx.send(resolve());
+            });
+            let result = match resolved_rx.recv_timeout(timeout) {
+                Ok(result) => Some(result),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    Some(Err(io::Error::other("the host name lookup panicked")))
+                }
+            };
+            let _ = tx.send(result);
+        });
+
+        Lookup {
+            host: host.to_owned(),
+            port,
+            result: rx
+        }
+    }
+
+    /// Waits for the lookup to finish and then hands the addresses to the
+    /// given handle, so that cURL does not resolve the host name again.
+    pub fn poll_resolve(&mut self, easy: &mut Easy) -> Poll<(), Error> {
+        let result = match self.result.poll() {
+            Ok(Async::Ready(result)) => result,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(_) => Some(Err(io::Error::other("the host name lookup was lost")))
+        };
+        let addrs = match result {
+            Some(Ok(ref addrs)) if !addrs.is_empty() => addrs,
+            Some(Ok(_)) => return Err(self.failed("no addresses found".to_owned())),
+            Some(Err(err)) => return Err(self.failed(err.to_string())),
+            None => return Err(Error::DnsTimeout(curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT)))
+        };
+
+        let addrs = addrs.iter()
+            .map(|addr| match *addr {
+                SocketAddr::V4(addr) => addr.ip().to_string(),
+                SocketAddr::V6(addr) => format!("[{}]", addr.ip())
+            })
+            .collect::<Vec<_>>();
+        // The leading `+` lets the addresses expire from the DNS cache of the
+        // session like any other lookup.
+        let mut list = List::new();
+        list.append(&format!("+{}:{}:{}", self.host, self.port, addrs.join(",")))?;
+        easy.resolve(list)?;
+        Ok(Async::Ready(()))
+    }
+
+    /// Creates the error for a failed lookup.
+    fn failed(&self, reason: String) -> Error {
+        let mut err = curl::Error::new(curl_sys::CURLE_COULDNT_RESOLVE_HOST);
+        err.set_extra(format!("Could not resolve host: {} ({})", self.host, reason));
+        Error::Resolve(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use curl;
+    use curl::easy::Easy;
+    use curl_sys;
+    use futures::future::poll_fn;
+    use futures::Future;
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve};
+    use super::{Lookup, Timeouts};
+
+    fn timed_out() -> curl::Error {
+        curl::Error::new(curl_sys::CURLE_OPERATION_TIMEDOUT)
+    }
+
+    fn timeouts(total: Option<Duration>, connect: Option<Duration>) -> Timeouts {
+        Timeouts {
+            connect,
+            ..Timeouts::new(total, None).unwrap()
+        }
+    }
+
+    #[test]
+    fn total_timeout_is_classified_by_elapsed_time() {
+        let timeouts = timeouts(Some(Duration::from_secs(5)), Some(Duration::from_secs(10)));
+        let at = |secs| timeouts.prepared + Duration::from_secs(secs);
+        match timeouts.classify_at(timed_out(), at(5)) {
+            Error::Timeout(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+        // A connect timeout shorter than the total one cannot be mistaken for it.
+        let timeouts = self::timeouts(Some(Duration::from_secs(5)), Some(Duration::from_millis(4950)));
+        match timeouts.classify_at(timed_out(), timeouts.prepared + Duration::from_millis(4950)) {
+            Error::ConnectTimeout(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn deadline_is_reported_when_it_shortened_the_timeout() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let timeouts = Timeouts::new(Some(Duration::from_secs(60)), Some(deadline)).unwrap();
+        assert!(timeouts.deadline);
+        match timeouts.classify_at(timed_out(), deadline) {
+            Error::Deadline(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+
+        let timeouts = Timeouts::new(Some(Duration::from_millis(100)), Some(deadline)).unwrap();
+        assert!(!timeouts.deadline);
+
+        match Timeouts::new(None, Some(Instant::now())) {
+            Err(Error::Deadline(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn low_speed_is_told_apart_from_connect_timeout_by_progress_reports() {
+        let timeouts = timeouts(Some(Duration::from_secs(60)), Some(Duration::from_secs(5)));
+        let start = timeouts.prepared;
+        match timeouts.classify_at(timed_out(), start + Duration::from_secs(5)) {
+            Error::ConnectTimeout(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+
+        // The connection was up, and cURL reported progress before checking the speed.
+        let mut watchdog = timeouts.watchdog();
+        assert!(watchdog.check_at(0.0, 0.0, start + Duration::from_secs(1)));
+        assert!(watchdog.check_at(0.0, 0.0, start + Duration::from_secs(20)));
+        match timeouts.classify_at(timed_out(), start + Duration::from_secs(20)) {
+            Error::LowSpeed(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+
+        // No reports since the previous hop, so the connection to the next one timed out.
+        match timeouts.classify_at(timed_out(), start + Duration::from_secs(25)) {
+            Error::ConnectTimeout(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn watchdog_trips_once_idle_after_connecting() {
+        let mut timeouts = timeouts(None, None);
+        timeouts.read_idle = Some(Duration::from_secs(2));
+        let start = timeouts.prepared;
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut watchdog = timeouts.watchdog();
+        // Connecting took longer than the read timeout, which does not count.
+        assert!(watchdog.check_at(0.0, 0.0, at(5_000)));
+        assert!(watchdog.check_at(0.0, 10.0, at(6_500)));
+        assert!(watchdog.check_at(100.0, 10.0, at(8_000)));
+        assert!(watchdog.check_at(100.0, 10.0, at(9_999)));
+        assert!(!watchdog.check_at(100.0, 10.0, at(10_000)));
+
+        match timeouts.classify_at(curl::Error::new(curl_sys::CURLE_ABORTED_BY_CALLBACK), at(10_000)) {
+            Error::ReadTimeout(_) => {},
+            other => panic!("unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn other_callback_aborts_are_not_timeouts() {
+        let timeouts = timeouts(None, None);
+        match timeouts.classify(curl::Error::new(curl_sys::CURLE_ABORTED_BY_CALLBACK)) {
+            Error::Curl(err) => assert!(err.is_aborted_by_callback()),
+            other => panic!("unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn arm_fails_once_the_total_timeout_is_used_up() {
+        let timeouts = timeouts(Some(Duration::from_millis(20)), None);
+        thread::sleep(Duration::from_millis(30));
+        match timeouts.arm(&mut Easy::new()) {
+            Err(Error::Timeout(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn lookup_times_out() {
+        let mut lookup = Lookup::with_resolver("slow.example", 80, Duration::from_millis(50), || {
+            thread::sleep(Duration::from_secs(2));
+            Ok(Vec::new())
+        });
+        let mut easy = Easy::new();
+        match poll_fn(|| lookup.poll_resolve(&mut easy)).wait() {
+            Err(Error::DnsTimeout(err)) => assert!(err.is_operation_timedout()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn lookup_reports_failures() {
+        let mut lookup = Lookup::with_resolver("missing.example", 80, Duration::from_secs(5), || {
+            Err(io::Error::other("no such host"))
+        });
+        let mut easy = Easy::new();
+        match poll_fn(|| lookup.poll_resolve(&mut easy)).wait() {
+            Err(Error::Resolve(err)) => {
+                assert!(err.is_couldnt_resolve_host());
+                assert!(err.extra_description().unwrap().contains("missing.example"));
+            },
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn resolves_before_sending() {
+        let mut url = serve(|_, stream| respond(stream, "200 OK", &[], b"resolved"));
+        url.set_host(Some("localhost")).unwrap();
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(url.as_str())
+            .dns_timeout(Duration::from_secs(5))
+            .send(evloop.handle());
+        assert_eq!(evloop.run(future).unwrap().body_str(), Some("resolved"));
+    }
+
+    #[test]
+    fn connect_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Once the accept queue is full, connecting hangs.
+        let mut queued = Vec::new();
+        for _ in 0..10_000 {
+            match TcpStream::connect_timeout(&addr, Duration::from_millis(100)) {
+                Ok(stream) => queued.push(stream),
+                Err(_) => break
+            }
+        }
+
+        let mut evloop = Core::new().unwrap();
+        let future = get(&format!("http://{}/", addr))
+            .connect_timeout(Duration::from_millis(300))
+            .timeout(Duration::from_secs(5))
+            .send(evloop.handle());
+        match evloop.run(future) {
+            Err(Error::ConnectTimeout(err)) => assert!(err.is_operation_timedout()),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
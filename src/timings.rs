@@ -0,0 +1,134 @@
+//! The module that contains the transfer metrics of a response.
+
+use std::time::Duration;
+
+use curl::easy::Easy;
+
+/// How long the phases of a transfer took and how much data it moved.
+///
+/// All points in time are measured from the start of the request, the way
+/// cURL reports them, so e.g. the time spent on the TLS handshake alone is
+/// `tls_handshake() - connect()`. When redirects were followed, they cover
+/// the last request only, except for `redirect()` and `total()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timings {
+    bytes_downloaded: u64,
+    bytes_uploaded: u64,
+    connect: Duration,
+    name_lookup: Duration,
+    redirect: Duration,
+    start_transfer: Duration,
+    tls_handshake: Duration,
+    total: Duration
+}
+
+impl Timings {
+    /// Reads the metrics of the last transfer from the given handle.
+    pub(crate) fn from_easy(easy: &Easy) -> Self {
+        let bytes = |size: Result<f64, _>| size.map(|size| size as u64).unwrap_or(0);
+        let time = |time: Result<Duration, _>| time.unwrap_or_default();
+        Timings {
+            bytes_downloaded: bytes(easy.download_size()),
+            bytes_uploaded: bytes(easy.upload_size()),
+            connect: time(easy.connect_time()),
+            name_lookup: time(easy.namelookup_time()),
+            redirect: time(easy.redirect_time()),
+            start_transfer: time(easy.starttransfer_time()),
+            tls_handshake: time(easy.appconnect_time()),
+            total: time(easy.total_time())
+        }
+    }
+
+    /// Gets the amount of body bytes received.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bytes_downloaded
+    }
+
+    /// Gets the amount of body bytes sent.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.bytes_uploaded
+    }
+
+    /// Gets the time until the connection to the server was established.
+    ///
+    /// This is close to the name lookup time if an existing connection was
+    /// reused.
+    pub fn connect(&self) -> Duration {
+        self.connect
+    }
+
+    /// Gets the average download speed over the whole transfer in bytes per
+    /// second.
+    pub fn download_speed(&self) -> f64 {
+        speed(self.bytes_downloaded, self.total)
+    }
+
+    /// Gets the time until the host name was resolved.
+    pub fn name_lookup(&self) -> Duration {
+        self.name_lookup
+    }
+
+    /// Gets the time spent following redirects before the last request
+    /// was started.
+    pub fn redirect(&self) -> Duration {
+        self.redirect
+    }
+
+    /// Gets the time until the first byte of the response arrived.
+    pub fn start_transfer(&self) -> Duration {
+        self.start_transfer
+    }
+
+    /// Gets the time until the TLS handshake was completed.
+    ///
+    /// This is zero for plain HTTP requests.
+    pub fn tls_handshake(&self) -> Duration {
+        self.tls_handshake
+    }
+
+    /// Gets the total time the request took, including redirects.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Gets the average upload speed over the whole transfer in bytes per
+    /// second.
+    pub fn upload_speed(&self) -> f64 {
+        speed(self.bytes_uploaded, self.total)
+    }
+}
+
+fn speed(bytes: u64, time: Duration) -> f64 {
+    let secs = time.as_secs() as f64 + f64::from(time.subsec_nanos()) / 1e9;
+    if secs > 0.0 {
+        bytes as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_core::reactor::Core;
+
+    use str::post;
+    use test_server::{respond, serve};
+
+    #[test]
+    fn measures_transfer() {
+        let url = serve(|request, stream| respond(stream, "200 OK", &[], &request.body));
+
+        let mut evloop = Core::new().unwrap();
+        let future = post(url.as_str()).body(vec![b'x'; 1024]).send(evloop.handle());
+        let timings = evloop.run(future).unwrap().timings();
+        assert_eq!(timings.bytes_downloaded(), 1024);
+        assert_eq!(timings.bytes_uploaded(), 1024);
+        assert_eq!(timings.tls_handshake(), Duration::from_secs(0));
+        assert!(timings.connect() <= timings.start_transfer());
+        assert!(timings.start_transfer() <= timings.total());
+        assert!(timings.total() > Duration::from_secs(0));
+        assert!(timings.download_speed() > 0.0);
+    }
+}
@@ -0,0 +1,349 @@
+//! The module that contains the TLS configuration.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::path::{Path, PathBuf};
+
+use curl::easy::{Easy, SslVersion};
+use error::Error;
+
+/// The encoding of a certificate or key file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CertFormat {
+    /// Base64-encoded with `-----BEGIN ...-----` lines.
+    Pem,
+    /// Binary ASN.1.
+    Der
+}
+
+/// A version of the TLS protocol.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    /// TLS 1.0
+    Tls10,
+    /// TLS 1.1
+    Tls11,
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13
+}
+
+/// The TLS settings for HTTPS requests.
+///
+/// By default, cURL verifies the server's certificate against the system's
+/// CA bundle and negotiates the TLS version and ciphers itself.
+///
+/// ```rust,ignore
+/// let tls = TlsConfig::new()
+///               .ca_file("/etc/internal/ca.pem")
+///               .client_cert("client.pem", CertFormat::Pem)
+///               .client_key("client.key", CertFormat::Pem)
+///               .min_version(TlsVersion::Tls12);
+/// let client = Client::new(evloop.handle()).tls(tls);
+/// ```
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    accept_invalid_certs: bool,
+    ca_dir: Option<PathBuf>,
+    ca_file: Option<PathBuf>,
+    ciphers: Option<String>,
+    client_cert: Option<(PathBuf, CertFormat)>,
+    client_key: Option<(PathBuf, CertFormat)>,
+    key_password: Option<String>,
+    min_version: Option<TlsVersion>,
+    pinned_public_key: Option<String>
+}
+
+impl TlsConfig {
+    /// Creates a new TLS configuration with cURL's defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the file with the CA certificates the server's certificate is
+    /// verified against, in PEM format.
+    pub fn ca_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.ca_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the directory with the CA certificates the server's certificate
+    /// is verified against.
+    ///
+    /// The directory must be prepared with OpenSSL's `c_rehash` utility.
+    pub fn ca_dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.ca_dir = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the list of ciphers to use, in the format of the TLS backend,
+    /// e.g. `ECDHE-RSA-AES128-GCM-SHA256:ECDHE-RSA-AES256-GCM-SHA384` for
+    /// OpenSSL.
+    pub fn ciphers(mut self, ciphers: &str) -> Self {
+        self.ciphers = Some(ciphers.to_owned());
+        self
+    }
+
+    /// Sets the certificate the client authenticates itself with.
+    pub fn client_cert<P: AsRef<Path>>(mut self, path: P, format: CertFormat) -> Self {
+        self.client_cert = Some((path.as_ref().to_owned(), format));
+        self
+    }
+
+    /// Sets the private key of the client certificate.
+    pub fn client_key<P: AsRef<Path>>(mut self, path: P, format: CertFormat) -> Self {
+        self.client_key = Some((path.as_ref().to_owned(), format));
+        self
+    }
+
+    /// Sets the password the private key of the client certificate is
+    /// encrypted with.
+    pub fn key_password(mut self, password: &str) -> Self {
+        self.key_password = Some(password.to_owned());
+        self
+    }
+
+    /// Sets the lowest TLS version that may be negotiated.
+    pub fn min_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = Some(version);
+        self
+    }
+
+    /// Only accepts servers whose public key matches the given one.
+    ///
+    /// The key is either the path to a PEM or DER file, or one or more
+    /// base64-encoded SHA-256 hashes like `sha256//YhKJKSzoTt2b5FP18fvpHo7fJYqQCjAa3HWY3tvRMwE=`,
+    /// separated by `;`. A mismatch fails the request with
+    /// [`Error::Tls`](enum.Error.html#variant.Tls).
+    pub fn pin_public_key(mut self, key: &str) -> Self {
+        self.pinned_public_key = Some(key.to_owned());
+        self
+    }
+
+    /// Accepts any certificate the server presents, even if it is expired,
+    /// self-signed or issued for another host.
+    ///
+    /// ## Warning
+    /// This makes HTTPS requests open to man-in-the-middle attacks. Only use
+    /// it for testing against local servers.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Configures the given handle with these settings.
+    pub(crate) fn configure(&self, easy: &mut Easy) -> Result<(), Error> {
+        if let Some(ref path) = self.ca_file {
+            easy.cainfo(path)?;
+        }
+        if let Some(ref path) = self.ca_dir {
+            easy.capath(path)?;
+        }
+        if let Some(ref ciphers) = self.ciphers {
+            easy.ssl_cipher_list(ciphers)?;
+        }
+        if let Some((ref path, format)) = self.client_cert {
+            easy.ssl_cert(path)?;
+            easy.ssl_cert_type(format.as_curl_type())?;
+        }
+        if let Some((ref path, format)) = self.client_key {
+            easy.ssl_key(path)?;
+            easy.ssl_key_type(format.as_curl_type())?;
+        }
+        if let Some(ref password) = self.key_password {
+            easy.key_password(password)?;
+        }
+        if let Some(version) = self.min_version {
+            easy.ssl_version(version.into())?;
+        }
+        if let Some(ref key) = self.pinned_public_key {
+            easy.pinned_public_key(key)?;
+        }
+        if self.accept_invalid_certs {
+            easy.ssl_verify_peer(false)?;
+            easy.ssl_verify_host(false)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for TlsConfig {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(TlsConfig))
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("ca_dir", &self.ca_dir)
+            .field("ca_file", &self.ca_file)
+            .field("ciphers", &self.ciphers)
+            .field("client_cert", &self.client_cert)
+            .field("client_key", &self.client_key)
+            .field("key_password", &self.key_password.as_ref().map(|_| "[redacted]"))
+            .field("min_version", &self.min_version)
+            .field("pinned_public_key", &self.pinned_public_key)
+            .finish()
+    }
+}
+
+impl CertFormat {
+    fn as_curl_type(self) -> &'static str {
+        match self {
+            CertFormat::Der => "DER",
+            CertFormat::Pem => "PEM"
+        }
+    }
+}
+
+impl From<TlsVersion> for SslVersion {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls10 => SslVersion::Tlsv10,
+            TlsVersion::Tls11 => SslVersion::Tlsv11,
+            TlsVersion::Tls12 => SslVersion::Tlsv12,
+            TlsVersion::Tls13 => SslVersion::Tlsv13
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::{SslAcceptor, SslMethod};
+    use openssl::x509::{X509, X509Name};
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use tokio_core::reactor::Core;
+
+    use error::Error;
+    use str::get;
+    use test_server::{respond, serve, TempPath};
+    use super::{TlsConfig, TlsVersion};
+
+    /// Generates a P-256 key pair.
+    fn generate_key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// Issues a certificate for the given key, signed by the given issuer or
+    /// by itself.
+    fn issue(name: &str, key: &PKey<Private>, issuer: Option<(&X509, &PKey<Private>)>) -> X509 {
+        let mut subject = X509Name::builder().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        cert.set_pubkey(key).unwrap();
+        match issuer {
+            Some((issuer, issuer_key)) => {
+                let san = SubjectAlternativeName::new()
+                    .ip("127.0.0.1")
+                    .build(&cert.x509v3_context(Some(issuer), None))
+                    .unwrap();
+                cert.append_extension(san).unwrap();
+                cert.set_issuer_name(issuer.subject_name()).unwrap();
+                cert.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            },
+            None => {
+                cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+                cert.set_issuer_name(&subject).unwrap();
+                cert.sign(key, MessageDigest::sha256()).unwrap();
+            }
+        }
+        cert.build()
+    }
+
+    /// Starts an HTTPS server whose certificate is signed by a CA of its
+    /// own, and returns its URL along with the CA certificate in PEM.
+    fn serve_tls() -> (String, Vec<u8>) {
+        let ca_key = generate_key();
+        let ca = issue("tokio-request test CA", &ca_key, None);
+        let key = generate_key();
+        let cert = issue("127.0.0.1", &key, Some((&ca, &ca_key)));
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = acceptor.accept(stream).unwrap();
+            let mut line = String::new();
+            {
+                let mut reader = BufReader::new(&mut stream);
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                }
+            }
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecure").unwrap();
+            let _ = stream.shutdown();
+        });
+        (url, ca.to_pem().unwrap())
+    }
+
+    #[test]
+    fn trusts_server_signed_by_custom_ca() {
+        let (url, ca) = serve_tls();
+        let ca_path = TempPath::new("tls-ca.pem");
+        fs::write(&ca_path, ca).unwrap();
+        let tls = TlsConfig::new()
+            .ca_file(&ca_path)
+            .min_version(TlsVersion::Tls12);
+
+        let mut evloop = Core::new().unwrap();
+        let response = evloop.run(get(&url).tls(tls).send(evloop.handle())).unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.body_str(), Some("secure"));
+    }
+
+    #[test]
+    fn failed_handshake_is_tls_error() {
+        // A plain HTTP server cannot complete a TLS handshake.
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], b""));
+        let url = url.as_str().replace("http://", "https://");
+        let tls = TlsConfig::new()
+            .danger_accept_invalid_certs(true)
+            .min_version(TlsVersion::Tls12);
+
+        let mut evloop = Core::new().unwrap();
+        match evloop.run(get(&url).tls(tls).send(evloop.handle())) {
+            Err(Error::Tls(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn missing_ca_file_is_tls_error() {
+        let url = serve(|_, stream| respond(stream, "200 OK", &[], b""));
+        let url = url.as_str().replace("http://", "https://");
+        let tls = TlsConfig::new().ca_file("/nonexistent/ca.pem");
+
+        let mut evloop = Core::new().unwrap();
+        match evloop.run(get(&url).tls(tls).send(evloop.handle())) {
+            Err(Error::Tls(_)) => {},
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn debug_redacts_key_password() {
+        let tls = TlsConfig::new().key_password("hunter2");
+        assert!(!format!("{:?}", tls).contains("hunter2"));
+    }
+}
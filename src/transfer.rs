@@ -0,0 +1,273 @@
+//! The module that contains the code driving a single transfer.
+
+use std::sync::{Arc, Mutex};
+
+use abort::{AbortHandle, Registration};
+use body::Upload;
+use cookie::{Cookie, CookieJar};
+use curl::easy::Easy;
+use curl_sys;
+use error::Error;
+use futures::{Async, Future, Poll};
+use pool::HandlePool;
+use timeout::{Lookup, Timeouts};
+use tokio_curl::{Perform, Session};
+
+/// A request that has been turned into a configured cURL handle, but
+/// has not been sent yet.
+pub(crate) struct Prepared {
+    /// The handle that cancels the request.
+    pub abort: Option<AbortHandle>,
+    /// The cookie jar of the request, along with the cookies handed to the handle.
+    pub cookies: Option<(CookieJar, Vec<Cookie>)>,
+    /// The configured handle.
+    pub easy: Easy,
+    /// The lookup of the host name, if it is resolved before the transfer starts.
+    pub lookup: Option<Lookup>,
+    /// The pool the handle goes back to once the request is done.
+    pub pool: Option<HandlePool>,
+    /// The timeouts of the request, used to classify errors.
+    pub timeouts: Timeouts,
+    /// The upload of the request body, if it is a stream.
+    pub upload: Option<Upload>
+}
+
+/// Drives a transfer along with its body stream, after resolving its host
+/// name if need be.
+pub(crate) struct Transfer {
+    abort: Option<Registration>,
+    cookies: Option<(CookieJar, Vec<Cookie>)>,
+    pool: Option<HandlePool>,
+    resume: Option<Resume>,
+    stage: Stage,
+    timeouts: Timeouts,
+    upload: Option<Upload>
+}
+
+/// Resumes a paused transfer.
+///
+/// cURL only picks up an unpaused transfer properly when `curl_easy_pause`
+/// is called from within the session's driver, so the call is made from
+/// the debug callback of a throwaway handle that is sent through the same
+/// session. That handle fails right away because it has no URL set.
+pub(crate) struct Resume {
+    easy: Arc<Mutex<Option<RawEasy>>>,
+    pending: Vec<Perform>,
+    session: Session
+}
+
+/// A raw handle to a running transfer. Only valid while the transfer is.
+struct RawEasy(*mut curl_sys::CURL);
+
+// SAFETY: The handle is only used to unpause the transfer from the thread
+// of the session that performs it, and is detached before it is freed.
+unsafe impl Send for RawEasy {}
+
+/// How far a transfer has come.
+enum Stage {
+    /// The handle waits for the lookup to finish.
+    Starting {
+        easy: Option<Easy>,
+        lookup: Lookup,
+        session: Session
+    },
+    /// The handle is being performed by the session.
+    Performing(Perform),
+    /// The transfer could not be started.
+    Failed(Option<Error>)
+}
+
+impl Prepared {
+    /// Starts performing the request through the given session.
+    pub fn perform(self, session: &Session) -> Transfer {
+        let mut transfer = Transfer {
+            abort: self.abort.map(|abort| abort.register()),
+            cookies: self.cookies,
+            pool: self.pool,
+            resume: None,
+            stage: Stage::Failed(None),
+            timeouts: self.timeouts,
+            upload: self.upload
+        };
+        transfer.stage = match self.lookup {
+            Some(lookup) => Stage::Starting {
+                easy: Some(self.easy),
+                lookup,
+                session: session.clone()
+            },
+            None => transfer.start(self.easy, session)
+        };
+        transfer
+    }
+}
+
+impl Future for Transfer {
+    type Item = Easy;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Easy, Error> {
+        // Dropping the transfer removes it from the session.
+        if self.abort.as_ref().is_some_and(Registration::poll_aborted) {
+            return Err(Error::Cancelled);
+        }
+        let started = match self.stage {
+            Stage::Starting { ref mut easy, ref mut lookup, ref session } => {
+                match lookup.poll_resolve(easy.as_mut().expect("Polled a failed transfer.")) {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(())) => Ok((easy.take().unwrap(), session.clone())),
+                    Err(error) => Err((easy.take().unwrap(), error))
+                }
+            },
+            Stage::Performing(_) => return self.poll_perform(),
+            Stage::Failed(ref mut error) => {
+                return Err(error.take().expect("Polled a failed transfer twice."));
+            }
+        };
+        self.stage = match started {
+            Ok((easy, session)) => self.start(easy, &session),
+            Err((easy, error)) => {
+                self.recycle(easy);
+                Stage::Failed(Some(error))
+            }
+        };
+        self.poll()
+    }
+}
+
+impl Transfer {
+    /// Hands the given handle to the session, with the time that is left of
+    /// the total timeout.
+    fn start(&mut self, mut easy: Easy, session: &Session) -> Stage {
+        match self.timeouts.arm(&mut easy) {
+            Ok(()) => {
+                self.resume = Some(Resume::new(&easy, session));
+                Stage::Performing(session.perform(easy))
+            },
+            Err(error) => {
+                self.recycle(easy);
+                Stage::Failed(Some(error))
+            }
+        }
+    }
+
+    /// Polls the running transfer.
+    fn poll_perform(&mut self) -> Poll<Easy, Error> {
+        if let (Some(upload), Some(resume)) = (self.upload.as_mut(), self.resume.as_mut()) {
+            if let Async::Ready(()) = upload.poll(resume)? {
+                // The read callback keeps what is left to send.
+                self.upload = None;
+            }
+        }
+
+        let res = match self.stage {
+            Stage::Performing(ref mut perform) => perform.poll(),
+            _ => unreachable!()
+        };
+        if let Ok(Async::NotReady) = res {
+            return Ok(Async::NotReady);
+        }
+        if let Some(ref mut resume) = self.resume {
+            resume.detach();
+        }
+        match res {
+            Ok(Async::Ready(mut easy)) => {
+                self.finish(&mut easy);
+                Ok(Async::Ready(easy))
+            },
+            Ok(Async::NotReady) => unreachable!(),
+            Err(mut error) => {
+                let easy = error.take_easy();
+                let error = match self.abort {
+                    Some(ref abort) if abort.is_aborted() => Error::Cancelled,
+                    _ => Error::from_transfer(error.into_error(), easy.as_ref(), &self.timeouts)
+                };
+                if let Some(mut easy) = easy {
+                    self.finish(&mut easy);
+                    self.recycle(easy);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Stores the cookies the server has set in the cookie jar.
+    fn finish(&mut self, easy: &mut Easy) {
+        if let Some((jar, sent)) = self.cookies.take() {
+            jar.collect(easy, &sent);
+        }
+    }
+
+    /// Gets the pool the handle of this transfer goes back to.
+    pub fn pool(&self) -> Option<&HandlePool> {
+        self.pool.as_ref()
+    }
+
+    /// Returns the handle of the finished transfer to the pool, if there is one.
+    pub fn recycle(&self, easy: Easy) {
+        if let Some(ref pool) = self.pool {
+            pool.checkin(easy);
+        }
+    }
+
+    /// Resumes the transfer after a callback has paused it.
+    ///
+    /// Does nothing if the transfer has not been started or has finished.
+    pub fn resume(&mut self) {
+        if let Some(ref mut resume) = self.resume {
+            resume.resume();
+        }
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        // Dropping the `Perform` cancels the transfer and frees the handle.
+        if let Some(ref mut resume) = self.resume {
+            resume.detach();
+        }
+    }
+}
+
+impl Resume {
+    /// Creates a `Resume` for the given handle, which is about to be performed
+    /// through the given session.
+    fn new(easy: &Easy, session: &Session) -> Self {
+        Resume {
+            easy: Arc::new(Mutex::new(Some(RawEasy(easy.raw())))),
+            pending: Vec::new(),
+            session: session.clone()
+        }
+    }
+
+    /// Resumes the paused transfer.
+    pub fn resume(&mut self) {
+        // Get rid of the handles of earlier resumptions
+        self.pending.retain_mut(|perform| matches!(perform.poll(), Ok(Async::NotReady)));
+
+        let easy = self.easy.clone();
+        let mut resumed = false;
+        let mut kick = Easy::new();
+        let config_res = kick.verbose(true)
+            .and_then(|_| kick.debug_function(move |_, _| {
+                if resumed {
+                    return;
+                }
+                resumed = true;
+                if let Some(ref raw) = *easy.lock().unwrap() {
+                    // SAFETY: The handle is attached, so its transfer is still running.
+                    unsafe {
+                        curl_sys::curl_easy_pause(raw.0, curl_sys::CURLPAUSE_RECV_CONT | curl_sys::CURLPAUSE_SEND_CONT);
+                    }
+                }
+            }));
+        config_res.expect("Failed to configure (native cURL) resume handle.");
+
+        self.pending.push(self.session.perform(kick));
+    }
+
+    /// Marks the transfer as finished, after which the raw handle must not
+    /// be touched anymore.
+    fn detach(&mut self) {
+        *self.easy.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,953 @@
+//! The module that contains the typed accessors for common headers.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use date::{format_http_date, parse_http_date};
+use error::Error;
+use header::{is_token, HeaderMap};
+use mime::Mime;
+use url::Url;
+use url::percent_encoding::percent_decode;
+
+/// The value of an `Authorization` header.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Authorization {
+    credentials: String,
+    scheme: String
+}
+
+/// The directives of a `Cache-Control` header.
+///
+/// ```rust,ignore
+/// let cache_control = CacheControl::new()
+///                         .directive("public", None)
+///                         .directive("max-age", Some("3600"));
+/// headers.set_cache_control(&cache_control).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheControl {
+    directives: Vec<(String, Option<String>)>
+}
+
+/// An authentication challenge from a `WWW-Authenticate` header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Challenge {
+    params: Vec<(String, String)>,
+    scheme: String,
+    token68: Option<String>
+}
+
+/// The value of a `Content-Disposition` header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContentDisposition {
+    kind: String,
+    params: Vec<(String, String)>
+}
+
+/// An entity tag from an `ETag` header.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ETag {
+    tag: String,
+    weak: bool
+}
+
+/// A link from a `Link` header, as described in RFC 8288.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Link {
+    params: Vec<(String, String)>,
+    url: String
+}
+
+impl HeaderMap {
+    /// Gets the media ranges of the `Accept` header, in the order they were
+    /// given.
+    ///
+    /// Media ranges that cannot be parsed are skipped.
+    pub fn accept(&self) -> Vec<Mime> {
+        self.get_all("Accept")
+            .flat_map(split_list)
+            .filter_map(|range| range.parse().ok())
+            .collect()
+    }
+
+    /// Gets the `Authorization` header.
+    pub fn authorization(&self) -> Option<Authorization> {
+        let mut parts = self.get("Authorization")?.trim().splitn(2, ' ');
+        let scheme = parts.next().filter(|scheme| !scheme.is_empty())?;
+        Some(Authorization::new(scheme, parts.next().unwrap_or("").trim()))
+    }
+
+    /// Gets the directives of all `Cache-Control` headers.
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        let mut directives = Vec::new();
+        for value in self.get_all("Cache-Control") {
+            let mut parser = Parser::new(value);
+            loop {
+                parser.skip(", \t");
+                let name = parser.token();
+                if name.is_empty() {
+                    break;
+                }
+                parser.skip(" \t");
+                let value = if parser.eat('=') {
+                    parser.skip(" \t");
+                    Some(parser.value())
+                } else {
+                    None
+                };
+                directives.push((name.to_ascii_lowercase(), value));
+            }
+        }
+        if directives.is_empty() {
+            None
+        } else {
+            Some(CacheControl { directives })
+        }
+    }
+
+    /// Gets the `Content-Disposition` header.
+    pub fn content_disposition(&self) -> Option<ContentDisposition> {
+        let mut parser = Parser::new(self.get("Content-Disposition")?);
+        parser.skip(" \t");
+        let kind = parser.token();
+        if kind.is_empty() {
+            return None;
+        }
+        Some(ContentDisposition {
+            kind: kind.to_ascii_lowercase(),
+            params: parser.params()
+        })
+    }
+
+    /// Gets the content codings of the `Content-Encoding` headers, in the
+    /// order they were applied.
+    pub fn content_encoding(&self) -> Vec<String> {
+        self.get_all("Content-Encoding")
+            .flat_map(split_list)
+            .map(|coding| coding.to_ascii_lowercase())
+            .collect()
+    }
+
+    /// Gets the `Content-Length` header.
+    pub fn content_length(&self) -> Option<u64> {
+        self.get("Content-Length")?.trim().parse().ok()
+    }
+
+    /// Gets the `Content-Type` header.
+    pub fn content_type(&self) -> Option<Mime> {
+        self.get("Content-Type")?.parse().ok()
+    }
+
+    /// Gets the `Date` header.
+    pub fn date(&self) -> Option<SystemTime> {
+        parse_http_date(self.get("Date")?)
+    }
+
+    /// Gets the `ETag` header.
+    pub fn etag(&self) -> Option<ETag> {
+        let value = self.get("ETag")?.trim();
+        let (weak, tag) = match value.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, value)
+        };
+        if tag.len() < 2 || !tag.starts_with('"') || !tag.ends_with('"') {
+            return None;
+        }
+        Some(ETag {
+            tag: tag[1..tag.len() - 1].to_owned(),
+            weak
+        })
+    }
+
+    /// Gets the `Expires` header.
+    ///
+    /// Invalid dates like `0` mean the response has already expired and are
+    /// returned as the Unix epoch.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.get("Expires").map(|value| parse_http_date(value).unwrap_or(UNIX_EPOCH))
+    }
+
+    /// Gets the `Last-Modified` header.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        parse_http_date(self.get("Last-Modified")?)
+    }
+
+    /// Gets the links of all `Link` headers.
+    pub fn link(&self) -> Vec<Link> {
+        let mut links = Vec::new();
+        for value in self.get_all("Link") {
+            let mut parser = Parser::new(value);
+            loop {
+                parser.skip(", \t");
+                if !parser.eat('<') {
+                    break;
+                }
+                let url = parser.until('>');
+                if !parser.eat('>') {
+                    break;
+                }
+                links.push(Link {
+                    params: parser.params(),
+                    url: url.trim().to_owned()
+                });
+            }
+        }
+        links
+    }
+
+    /// Gets the `Location` header.
+    ///
+    /// Relative URLs are returned as they are.
+    pub fn location(&self) -> Option<&str> {
+        self.get("Location")
+    }
+
+    /// Gets the challenges of all `WWW-Authenticate` headers.
+    pub fn www_authenticate(&self) -> Vec<Challenge> {
+        let mut challenges: Vec<Challenge> = Vec::new();
+        for value in self.get_all("WWW-Authenticate") {
+            let mut parser = Parser::new(value);
+            let mut current: Option<Challenge> = None;
+            loop {
+                parser.skip(", \t");
+                let token = parser.token();
+                if token.is_empty() {
+                    break;
+                }
+                parser.skip(" \t");
+                match current {
+                    Some(ref mut challenge) if parser.eat('=') => {
+                        parser.skip(" \t");
+                        challenge.params.push((token.to_ascii_lowercase(), parser.value()));
+                        continue;
+                    },
+                    _ => {}
+                }
+
+                challenges.extend(current.take());
+                let mut challenge = Challenge::new(token);
+                let start = parser.pos;
+                let token68 = parser.token68();
+                parser.skip(" \t");
+                if !token68.is_empty() && (parser.is_done() || parser.peek() == Some(',')) {
+                    challenge.token68 = Some(token68.to_owned());
+                } else {
+                    parser.pos = start;
+                }
+                current = Some(challenge);
+            }
+            challenges.extend(current);
+        }
+        challenges
+    }
+
+    /// Sets the `Accept` header.
+    pub fn set_accept(&mut self, ranges: &[Mime]) -> Result<(), Error> {
+        let value: Vec<String> = ranges.iter().map(ToString::to_string).collect();
+        self.insert("Accept", &value.join(", "))
+    }
+
+    /// Sets the `Authorization` header.
+    pub fn set_authorization(&mut self, authorization: &Authorization) -> Result<(), Error> {
+        self.insert("Authorization", &authorization.to_string())
+    }
+
+    /// Sets the `Cache-Control` header.
+    pub fn set_cache_control(&mut self, cache_control: &CacheControl) -> Result<(), Error> {
+        self.insert("Cache-Control", &cache_control.to_string())
+    }
+
+    /// Sets the `Content-Disposition` header.
+    pub fn set_content_disposition(&mut self, disposition: &ContentDisposition) -> Result<(), Error> {
+        self.insert("Content-Disposition", &disposition.to_string())
+    }
+
+    /// Sets the `Content-Encoding` header.
+    pub fn set_content_encoding(&mut self, codings: &[&str]) -> Result<(), Error> {
+        self.insert("Content-Encoding", &codings.join(", "))
+    }
+
+    /// Sets the `Content-Length` header.
+    pub fn set_content_length(&mut self, len: u64) -> Result<(), Error> {
+        self.insert("Content-Length", &len.to_string())
+    }
+
+    /// Sets the `Content-Type` header.
+    pub fn set_content_type(&mut self, mime: &Mime) -> Result<(), Error> {
+        self.insert("Content-Type", &mime.to_string())
+    }
+
+    /// Sets the `Date` header.
+    pub fn set_date(&mut self, date: SystemTime) -> Result<(), Error> {
+        self.insert("Date", &format_http_date(date))
+    }
+
+    /// Sets the `ETag` header.
+    pub fn set_etag(&mut self, etag: &ETag) -> Result<(), Error> {
+        self.insert("ETag", &etag.to_string())
+    }
+
+    /// Sets the `Expires` header.
+    pub fn set_expires(&mut self, expires: SystemTime) -> Result<(), Error> {
+        self.insert("Expires", &format_http_date(expires))
+    }
+
+    /// Sets the `Last-Modified` header.
+    pub fn set_last_modified(&mut self, last_modified: SystemTime) -> Result<(), Error> {
+        self.insert("Last-Modified", &format_http_date(last_modified))
+    }
+
+    /// Sets the `Link` header.
+    pub fn set_link(&mut self, links: &[Link]) -> Result<(), Error> {
+        let value: Vec<String> = links.iter().map(ToString::to_string).collect();
+        self.insert("Link", &value.join(", "))
+    }
+
+    /// Sets the `Location` header.
+    pub fn set_location(&mut self, url: &Url) -> Result<(), Error> {
+        self.insert("Location", url.as_str())
+    }
+
+    /// Sets the `WWW-Authenticate` header.
+    pub fn set_www_authenticate(&mut self, challenges: &[Challenge]) -> Result<(), Error> {
+        let value: Vec<String> = challenges.iter().map(ToString::to_string).collect();
+        self.insert("WWW-Authenticate", &value.join(", "))
+    }
+}
+
+impl Authorization {
+    /// Creates a new `Authorization` header with the given scheme and
+    /// credentials.
+    pub fn new(scheme: &str, credentials: &str) -> Self {
+        Authorization {
+            credentials: credentials.to_owned(),
+            scheme: scheme.to_owned()
+        }
+    }
+
+    /// Creates a new `Authorization` header for HTTP Basic authentication.
+    pub fn basic(user: &str, password: &str) -> Self {
+        Self::new("Basic", &base64_encode(format!("{}:{}", user, password).as_bytes()))
+    }
+
+    /// Creates a new `Authorization` header with the given bearer token.
+    pub fn bearer(token: &str) -> Self {
+        Self::new("Bearer", token)
+    }
+
+    /// Gets the credentials, e.g. the base64-encoded user name and password
+    /// for Basic authentication.
+    pub fn credentials(&self) -> &str {
+        &self.credentials
+    }
+
+    /// Gets the authentication scheme, e.g. `Basic`.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+}
+
+impl Debug for Authorization {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct(stringify!(Authorization))
+            .field("credentials", &"[redacted]")
+            .field("scheme", &self.scheme)
+            .finish()
+    }
+}
+
+impl Display for Authorization {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{} {}", self.scheme, self.credentials)
+    }
+}
+
+impl CacheControl {
+    /// Creates a new, empty set of directives.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directive with an optional value.
+    pub fn directive(mut self, name: &str, value: Option<&str>) -> Self {
+        self.directives.push((name.to_ascii_lowercase(), value.map(str::to_owned)));
+        self
+    }
+
+    /// Checks whether the given directive is present.
+    pub fn has(&self, name: &str) -> bool {
+        self.directives.iter().any(|kvp| kvp.0.eq_ignore_ascii_case(name))
+    }
+
+    /// Checks whether the `must-revalidate` directive is present.
+    pub fn must_revalidate(&self) -> bool {
+        self.has("must-revalidate")
+    }
+
+    /// Gets the `max-age` directive.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.value("max-age")?.parse().ok().map(Duration::from_secs)
+    }
+
+    /// Checks whether the `no-cache` directive is present.
+    pub fn no_cache(&self) -> bool {
+        self.has("no-cache")
+    }
+
+    /// Checks whether the `no-store` directive is present.
+    pub fn no_store(&self) -> bool {
+        self.has("no-store")
+    }
+
+    /// Checks whether the `private` directive is present.
+    pub fn is_private(&self) -> bool {
+        self.has("private")
+    }
+
+    /// Checks whether the `public` directive is present.
+    pub fn is_public(&self) -> bool {
+        self.has("public")
+    }
+
+    /// Gets the value of the given directive.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.directives.iter()
+            .find(|kvp| kvp.0.eq_ignore_ascii_case(name))
+            .and_then(|kvp| kvp.1.as_ref())
+            .map(String::as_str)
+    }
+}
+
+impl Display for CacheControl {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        for (i, (name, value)) in self.directives.iter().enumerate() {
+            if i > 0 {
+                fmt.write_str(", ")?;
+            }
+            fmt.write_str(name)?;
+            if let Some(ref value) = *value {
+                write!(fmt, "={}", quote(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Challenge {
+    /// Creates a new challenge with the given scheme and no parameters.
+    pub fn new(scheme: &str) -> Self {
+        Challenge {
+            params: Vec::new(),
+            scheme: scheme.to_owned(),
+            token68: None
+        }
+    }
+
+    /// Adds a parameter to the challenge.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_ascii_lowercase(), value.to_owned()));
+        self
+    }
+
+    /// Gets the value of the given parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        get_param(&self.params, name)
+    }
+
+    /// Gets the `realm` parameter.
+    pub fn realm(&self) -> Option<&str> {
+        self.get("realm")
+    }
+
+    /// Gets the authentication scheme, e.g. `Digest`.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Gets the opaque token some schemes send instead of parameters.
+    pub fn token68(&self) -> Option<&str> {
+        self.token68.as_deref()
+    }
+}
+
+impl Display for Challenge {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.write_str(&self.scheme)?;
+        if let Some(ref token68) = self.token68 {
+            write!(fmt, " {}", token68)?;
+        }
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            write!(fmt, "{}{}={}", if i == 0 { " " } else { ", " }, name, quote(value))?;
+        }
+        Ok(())
+    }
+}
+
+impl ContentDisposition {
+    /// Creates a new `Content-Disposition` header of the given kind, e.g.
+    /// `attachment`.
+    pub fn new(kind: &str) -> Self {
+        ContentDisposition {
+            kind: kind.to_ascii_lowercase(),
+            params: Vec::new()
+        }
+    }
+
+    /// Creates a new `Content-Disposition` header for a download that is
+    /// saved under the given file name.
+    pub fn attachment(file_name: &str) -> Self {
+        let disposition = Self::new("attachment");
+        if file_name.is_ascii() {
+            disposition.param("filename", file_name)
+        } else {
+            // Older clients only understand `filename`, so give them an ASCII fallback.
+            let fallback: String = file_name.chars().map(|c| if c.is_ascii() { c } else { '_' }).collect();
+            disposition.param("filename", &fallback)
+                       .param("filename*", &format!("UTF-8''{}", encode_ext_value(file_name)))
+        }
+    }
+
+    /// Adds a parameter to the header.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_ascii_lowercase(), value.to_owned()));
+        self
+    }
+
+    /// Gets the file name the content should be saved under.
+    ///
+    /// The `filename*` parameter takes precedence over `filename`.
+    pub fn file_name(&self) -> Option<String> {
+        self.get("filename*")
+            .and_then(decode_ext_value)
+            .or_else(|| self.get("filename").map(str::to_owned))
+    }
+
+    /// Gets the value of the given parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        get_param(&self.params, name)
+    }
+
+    /// Checks whether the content should be downloaded instead of displayed.
+    pub fn is_attachment(&self) -> bool {
+        self.kind == "attachment"
+    }
+
+    /// Gets the kind of disposition, e.g. `inline` or `attachment`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+}
+
+impl Display for ContentDisposition {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.write_str(&self.kind)?;
+        for (name, value) in &self.params {
+            if name.ends_with('*') {
+                write!(fmt, "; {}={}", name, value)?;
+            } else {
+                write!(fmt, "; {}={}", name, quote(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ETag {
+    /// Creates a new strong entity tag.
+    pub fn strong(tag: &str) -> Self {
+        ETag {
+            tag: tag.to_owned(),
+            weak: false
+        }
+    }
+
+    /// Creates a new weak entity tag.
+    pub fn weak(tag: &str) -> Self {
+        ETag {
+            tag: tag.to_owned(),
+            weak: true
+        }
+    }
+
+    /// Checks whether the entity tag is weak.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Gets the tag without the quotes and the weakness indicator.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Compares the tags, ignoring whether they are weak.
+    pub fn weak_eq(&self, other: &ETag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl Display for ETag {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        if self.weak {
+            fmt.write_str("W/")?;
+        }
+        write!(fmt, "\"{}\"", self.tag)
+    }
+}
+
+impl Link {
+    /// Creates a new link to the given URL.
+    pub fn new(url: &str) -> Self {
+        Link {
+            params: Vec::new(),
+            url: url.to_owned()
+        }
+    }
+
+    /// Adds a parameter to the link, e.g. `rel`.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_ascii_lowercase(), value.to_owned()));
+        self
+    }
+
+    /// Gets the value of the given parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        get_param(&self.params, name)
+    }
+
+    /// Gets the relation type of the link, e.g. `next`.
+    pub fn rel(&self) -> Option<&str> {
+        self.get("rel")
+    }
+
+    /// Gets the URL of the link.
+    ///
+    /// Relative URLs are returned as they are.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Display for Link {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "<{}>", self.url)?;
+        for (name, value) in &self.params {
+            write!(fmt, "; {}={}", name, quote(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// A cursor over a header value.
+struct Parser<'a> {
+    pos: usize,
+    value: &'a str
+}
+
+impl<'a> Parser<'a> {
+    fn new(value: &'a str) -> Self {
+        Parser { pos: 0, value }
+    }
+
+    /// Consumes the given character, if it is next.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.value.len()
+    }
+
+    /// Parses `; name=value` parameters up to the next comma.
+    fn params(&mut self) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+        loop {
+            self.skip(" \t");
+            if !self.eat(';') {
+                break;
+            }
+            self.skip(" \t");
+            let name = self.token();
+            if name.is_empty() {
+                break;
+            }
+            self.skip(" \t");
+            let value = if self.eat('=') {
+                self.skip(" \t");
+                self.value()
+            } else {
+                String::new()
+            };
+            params.push((name.to_ascii_lowercase(), value));
+        }
+        params
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.value[self.pos..].chars().next()
+    }
+
+    /// Skips all of the given characters.
+    fn skip(&mut self, chars: &str) {
+        self.take_while(|c| chars.contains(c));
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, pred: F) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.value[start..self.pos]
+    }
+
+    fn token(&mut self) -> &'a str {
+        self.take_while(|c| c.is_ascii() && is_token(c.encode_utf8(&mut [0; 4])))
+    }
+
+    fn token68(&mut self) -> &'a str {
+        let start = self.pos;
+        self.take_while(|c| c.is_ascii_alphanumeric() || "-._~+/".contains(c));
+        self.take_while(|c| c == '=');
+        &self.value[start..self.pos]
+    }
+
+    fn until(&mut self, end: char) -> &'a str {
+        self.take_while(|c| c != end)
+    }
+
+    /// Parses a token or a quoted string.
+    fn value(&mut self) -> String {
+        if !self.eat('"') {
+            return self.token().to_owned();
+        }
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                '"' => break,
+                '\\' => if let Some(escaped) = self.peek() {
+                    self.pos += escaped.len_utf8();
+                    value.push(escaped);
+                },
+                c => value.push(c)
+            }
+        }
+        value
+    }
+}
+
+/// Encodes bytes with the standard base64 alphabet and padding.
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes an RFC 8187 extended value like `UTF-8''na%C3%AFve.txt`.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+    if !charset.eq_ignore_ascii_case("UTF-8") {
+        return None;
+    }
+    percent_decode(encoded.as_bytes()).decode_utf8().ok().map(|decoded| decoded.into_owned())
+}
+
+/// Percent-encodes all characters that are not allowed in RFC 8187 extended
+/// values.
+fn encode_ext_value(value: &str) -> String {
+    let mut encoded = String::new();
+    for &b in value.as_bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+fn get_param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params.iter()
+        .find(|kvp| kvp.0.eq_ignore_ascii_case(name))
+        .map(|kvp| kvp.1.as_str())
+}
+
+/// Quotes the given value, unless it is a token.
+fn quote(value: &str) -> String {
+    if is_token(value) {
+        value.to_owned()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Splits a comma-separated list, leaving out empty elements.
+///
+/// Commas in quoted strings do not separate elements.
+fn split_list(value: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                elements.push(&value[start..i]);
+                start = i + 1;
+            },
+            _ => {}
+        }
+    }
+    elements.push(&value[start..]);
+    elements.into_iter()
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use header::HeaderMap;
+    use url::Url;
+    use super::{Authorization, CacheControl, Challenge, ContentDisposition, ETag, Link};
+
+    fn headers(entries: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in entries {
+            headers.append(name, value).unwrap();
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_simple_headers() {
+        let headers = headers(&[
+            ("Accept", "text/html, application/json;q=0.9"),
+            ("Authorization", "Bearer abc.def"),
+            ("content-length", " 42 "),
+            ("Content-Encoding", "gzip, BR"),
+            ("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ("ETag", "W/\"xyzzy\""),
+            ("Expires", "0"),
+            ("Location", "/next")
+        ]);
+        assert_eq!(headers.accept().len(), 2);
+        let authorization = headers.authorization().unwrap();
+        assert_eq!((authorization.scheme(), authorization.credentials()), ("Bearer", "abc.def"));
+        assert!(!format!("{:?}", authorization).contains("abc.def"));
+        assert_eq!(headers.content_length(), Some(42));
+        assert_eq!(headers.content_encoding(), vec!["gzip", "br"]);
+        assert_eq!(headers.date(), Some(UNIX_EPOCH + Duration::from_secs(784_111_777)));
+        assert_eq!(headers.etag(), Some(ETag::weak("xyzzy")));
+        assert_eq!(headers.expires(), Some(UNIX_EPOCH));
+        assert_eq!(headers.last_modified(), None);
+        assert_eq!(headers.location(), Some("/next"));
+    }
+
+    #[test]
+    fn splits_lists_outside_quoted_strings() {
+        let headers = headers(&[("Accept", "text/html;level=\"1,2\", text/plain;x=\"a\\\",b\", application/json")]);
+        let accept: Vec<_> = headers.accept().iter().map(ToString::to_string).collect();
+        assert_eq!(accept.len(), 3, "{:?}", accept);
+        assert_eq!(accept[2], "application/json");
+    }
+
+    #[test]
+    fn parses_structured_headers() {
+        let headers = headers(&[
+            ("Cache-Control", "public, max-age=\"3600\""),
+            ("Cache-Control", "Must-Revalidate"),
+            ("Content-Disposition", "attachment; filename=\"a \\\"b\\\".txt\"; filename*=UTF-8''na%C3%AFve.txt"),
+            ("Link", "<https://api.example.com/?page=2>; rel=\"next\", </?page=9>; rel=last; title=\"a, b\""),
+            ("WWW-Authenticate", "Digest realm=\"test\", qop=\"auth,auth-int\", nonce=abc, Negotiate, Basic realm=x, NTLM dG9rZW4=")
+        ]);
+
+        let cache_control = headers.cache_control().unwrap();
+        assert!(cache_control.is_public() && cache_control.must_revalidate() && !cache_control.no_store());
+        assert_eq!(cache_control.max_age(), Some(Duration::from_secs(3600)));
+
+        let disposition = headers.content_disposition().unwrap();
+        assert!(disposition.is_attachment());
+        assert_eq!(disposition.get("filename"), Some("a \"b\".txt"));
+        assert_eq!(disposition.file_name(), Some("na\u{ef}ve.txt".to_owned()));
+
+        let links = headers.link();
+        assert_eq!(links, vec![
+            Link::new("https://api.example.com/?page=2").param("rel", "next"),
+            Link::new("/?page=9").param("rel", "last").param("title", "a, b")
+        ]);
+
+        let challenges = headers.www_authenticate();
+        assert_eq!(challenges, vec![
+            Challenge::new("Digest").param("realm", "test").param("qop", "auth,auth-int").param("nonce", "abc"),
+            Challenge::new("Negotiate"),
+            Challenge::new("Basic").param("realm", "x"),
+            Challenge { params: Vec::new(), scheme: "NTLM".to_owned(), token68: Some("dG9rZW4=".to_owned()) }
+        ]);
+    }
+
+    #[test]
+    fn setters_round_trip() {
+        let mut headers = HeaderMap::new();
+        let date = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        let cache_control = CacheControl::new().directive("no-cache", None).directive("max-age", Some("60"));
+        let disposition = ContentDisposition::attachment("r\u{e9}sum\u{e9}.pdf");
+        let links = vec![Link::new("/a").param("rel", "next")];
+        let challenges = vec![Challenge::new("Basic").param("realm", "my realm")];
+
+        headers.set_accept(&["text/plain".parse().unwrap()]).unwrap();
+        headers.set_authorization(&Authorization::basic("user", "pass")).unwrap();
+        headers.set_cache_control(&cache_control).unwrap();
+        headers.set_content_disposition(&disposition).unwrap();
+        headers.set_content_encoding(&["gzip"]).unwrap();
+        headers.set_content_length(7).unwrap();
+        headers.set_date(date).unwrap();
+        headers.set_etag(&ETag::strong("v1")).unwrap();
+        headers.set_expires(date).unwrap();
+        headers.set_last_modified(date).unwrap();
+        headers.set_link(&links).unwrap();
+        headers.set_location(&Url::parse("https://example.com/next").unwrap()).unwrap();
+        headers.set_www_authenticate(&challenges).unwrap();
+
+        assert_eq!(headers.get("Authorization"), Some("Basic dXNlcjpwYXNz"));
+        assert_eq!(headers.get("Content-Disposition"),
+                   Some("attachment; filename=r_sum_.pdf; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"));
+        assert_eq!(headers.get("Date"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(headers.accept().len(), 1);
+        assert_eq!(headers.cache_control(), Some(cache_control));
+        assert_eq!(headers.content_disposition().unwrap().file_name(), Some("r\u{e9}sum\u{e9}.pdf".to_owned()));
+        assert_eq!(headers.content_encoding(), vec!["gzip"]);
+        assert_eq!(headers.content_length(), Some(7));
+        assert_eq!(headers.etag(), Some(ETag::strong("v1")));
+        assert_eq!((headers.date(), headers.expires(), headers.last_modified()), (Some(date), Some(date), Some(date)));
+        assert_eq!(headers.link(), links);
+        assert_eq!(headers.location(), Some("https://example.com/next"));
+        assert_eq!(headers.www_authenticate(), challenges);
+    }
+}